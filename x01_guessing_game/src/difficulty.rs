@@ -0,0 +1,98 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+
+/// Ordered from the easiest
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard];
+
+    pub fn parse(text: &str) -> Option<Difficulty> {
+        match text.trim().to_lowercase().as_str() {
+            "e" | "easy" => Some(Difficulty::Easy),
+            "m" | "medium" => Some(Difficulty::Medium),
+            "h" | "hard" => Some(Difficulty::Hard),
+            _ => None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Hard => "hard"
+        }
+    }
+
+    /// Numbers the secret is drawn from
+    pub fn range(self) -> RangeInclusive<u32> {
+        match self {
+            Difficulty::Easy => 1..=50,
+            Difficulty::Medium => 1..=100,
+            Difficulty::Hard => 1..=1000
+        }
+    }
+
+    /// Medium and hard leave no room for error, a binary search needs every
+    /// attempt of them in the worst case
+    pub fn max_attempts(self) -> u32 {
+        match self {
+            Difficulty::Easy => 10,
+            Difficulty::Medium => 7,
+            Difficulty::Hard => 10
+        }
+    }
+
+    fn multiplier(self) -> u32 {
+        match self {
+            Difficulty::Easy => 1,
+            Difficulty::Medium => 2,
+            Difficulty::Hard => 4
+        }
+    }
+
+    /// Score of a win after `attempts` guesses, from `100 * multiplier` for
+    /// a first guess down to a tenth of that or so for the last attempt
+    pub fn score(self, attempts: u32) -> u32 {
+        let remaining = self.max_attempts().saturating_sub(attempts) + 1;
+
+        100 * self.multiplier() * remaining / self.max_attempts()
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({}..{}, {} attempts)", self.name(), self.range().start(), self.range().end(), self.max_attempts())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fewer_attempts_score_higher() {
+        for difficulty in Difficulty::ALL.iter() {
+            let scores : Vec<u32> = (1..=difficulty.max_attempts()).map(|attempts| difficulty.score(attempts)).collect();
+
+            assert!(scores.windows(2).all(|pair| pair[0] > pair[1]), "{:?}", scores);
+            assert!(*scores.last().unwrap() > 0);
+        }
+
+        assert_eq!(Difficulty::Easy.score(1), 100);
+        assert_eq!(Difficulty::Hard.score(1), 400);
+        assert_eq!(Difficulty::Medium.score(7), 28);
+    }
+
+    #[test]
+    fn difficulties_parse_from_names_and_initials() {
+        assert_eq!(Difficulty::parse("H\n"), Some(Difficulty::Hard));
+        assert_eq!(Difficulty::parse("medium"), Some(Difficulty::Medium));
+        assert_eq!(Difficulty::parse("impossible"), None);
+    }
+}
@@ -0,0 +1,155 @@
+use std::cmp::Ordering;
+use std::error;
+use std::fmt;
+
+use rand::Rng;
+
+use crate::difficulty::Difficulty;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum State {
+    Playing,
+    Won,
+    /// Every attempt was used without guessing the secret
+    Lost
+}
+
+/// Answer to a guess
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Feedback {
+    /// How the guess compares to the secret, `Less` means too small
+    pub ordering: Ordering,
+    /// The state of the game after the guess
+    pub state: State
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum GameError {
+    /// Guesses outside of the range do not use an attempt
+    OutOfRange{guess: u32, start: u32, end: u32},
+    Over
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameError::OutOfRange{guess, start, end} => write!(f, "{} is not between {} and {}", guess, start, end),
+            GameError::Over => write!(f, "The game is over")
+        }
+    }
+}
+
+impl error::Error for GameError {}
+
+/// One round of the guessing game, independent of where the guesses come from
+#[derive(Clone, Debug)]
+pub struct Game {
+    difficulty: Difficulty,
+    secret: u32,
+    attempts: u32,
+    state: State
+}
+
+impl Game {
+    /// Draws the secret from the range of `difficulty` with `rng`
+    pub fn new<R: Rng>(difficulty: Difficulty, rng: &mut R) -> Game {
+        let range = difficulty.range();
+        let secret = rng.gen_range(*range.start(), *range.end() + 1);
+
+        Game::with_secret(difficulty, secret)
+    }
+
+    pub fn with_secret(difficulty: Difficulty, secret: u32) -> Game {
+        Game{difficulty, secret, attempts: 0, state: State::Playing}
+    }
+
+    pub fn difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+
+    pub fn secret(&self) -> u32 {
+        self.secret
+    }
+
+    /// Attempts used so far
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    pub fn guess(&mut self, guess: u32) -> Result<Feedback, GameError> {
+        if self.state != State::Playing {
+            return Err(GameError::Over);
+        }
+
+        let range = self.difficulty.range();
+
+        if !range.contains(&guess) {
+            return Err(GameError::OutOfRange{guess, start: *range.start(), end: *range.end()});
+        }
+
+        self.attempts += 1;
+
+        let ordering = guess.cmp(&self.secret);
+
+        self.state = match ordering {
+            Ordering::Equal => State::Won,
+            _ if self.attempts == self.difficulty.max_attempts() => State::Lost,
+            _ => State::Playing
+        };
+
+        Ok(Feedback{ordering, state: self.state})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn guesses_compare_to_the_secret() {
+        let mut game = Game::with_secret(Difficulty::Medium, 42);
+
+        assert_eq!(game.guess(50), Ok(Feedback{ordering: Ordering::Greater, state: State::Playing}));
+        assert_eq!(game.guess(25), Ok(Feedback{ordering: Ordering::Less, state: State::Playing}));
+        assert_eq!(game.guess(42), Ok(Feedback{ordering: Ordering::Equal, state: State::Won}));
+        assert_eq!(game.attempts(), 3);
+        assert_eq!(game.guess(42), Err(GameError::Over));
+    }
+
+    #[test]
+    fn running_out_of_attempts_loses() {
+        let mut game = Game::with_secret(Difficulty::Medium, 100);
+
+        for _ in 1..Difficulty::Medium.max_attempts() {
+            assert_eq!(game.guess(1).unwrap().state, State::Playing);
+        }
+
+        assert_eq!(game.guess(1), Ok(Feedback{ordering: Ordering::Less, state: State::Lost}));
+    }
+
+    #[test]
+    fn out_of_range_guesses_are_free() {
+        let mut game = Game::with_secret(Difficulty::Easy, 7);
+
+        assert_eq!(game.guess(51), Err(GameError::OutOfRange{guess: 51, start: 1, end: 50}));
+        assert_eq!(game.guess(0).unwrap_err().to_string(), "0 is not between 1 and 50");
+        assert_eq!(game.attempts(), 0);
+    }
+
+    #[test]
+    fn seeds_are_deterministic() {
+        let secrets = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..5).map(|_| Game::new(Difficulty::Hard, &mut rng).secret()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(secrets(7), secrets(7));
+        assert!(secrets(7).iter().all(|secret| Difficulty::Hard.range().contains(secret)));
+    }
+}
@@ -1,29 +1,127 @@
+mod difficulty;
+mod game;
+mod scores;
+mod session;
+mod solver;
+
+use std::env;
 use std::io;
-use std::cmp::Ordering;
-use rand::Rng;
+use std::path::PathBuf;
+use std::process;
+use rand::{FromEntropy, SeedableRng};
+use rand::rngs::StdRng;
+
+use difficulty::Difficulty;
+use scores::ScoreTable;
+use session::Session;
+
+const USAGE: &str = "Usage: x01_guessing_game [OPTIONS]
+
+Options:
+  --difficulty NAME     easy, medium or hard, asked before every game if not given
+  --reverse             think of a number and let the computer guess it
+  --simulate GAMES      let the computer solve GAMES games of each difficulty and report its average guesses
+  --scores PATH         file to keep the best scores in (default: guessing_game_scores.txt)
+  --seed N              draw the secrets from a generator seeded with N, to replay the same games
+  -h, --help            print this help";
+
+const DEFAULT_SCORES_PATH: &str = "guessing_game_scores.txt";
+
+/// What the program does
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mode {
+    /// The player guesses the computer's number
+    Guess,
+    /// The computer guesses the player's number
+    Reverse,
+    Simulate{games: u32}
+}
+
+struct Options {
+    mode: Mode,
+    difficulty: Option<Difficulty>,
+    scores: PathBuf,
+    seed: Option<u64>
+}
+
+/// Reads the options from the command line arguments, `None` if the help
+/// was asked for
+fn parse_args(args: &[String]) -> Result<Option<Options>, String> {
+    let mut options = Options{mode: Mode::Guess, difficulty: None, scores: PathBuf::from(DEFAULT_SCORES_PATH), seed: None};
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--difficulty" => {
+                let name = args.next().ok_or("'--difficulty' expects a name")?;
+                options.difficulty = Some(Difficulty::parse(name).ok_or_else(|| format!("Unknown difficulty: '{}'", name))?);
+            },
+            "--reverse" => options.mode = Mode::Reverse,
+            "--simulate" => {
+                options.mode = match args.next().map(|text| text.parse()) {
+                    Some(Ok(games)) if games > 0 => Mode::Simulate{games},
+                    _ => return Err(String::from("'--simulate' expects a positive number of games"))
+                };
+            },
+            "--scores" => options.scores = PathBuf::from(args.next().ok_or("'--scores' expects a path")?),
+            "--seed" => {
+                let seed = args.next().ok_or("'--seed' expects a number")?;
+                options.seed = Some(seed.parse().map_err(|_| format!("Expected the seed to be a non-negative integer, got '{}'", seed))?);
+            },
+            text => return Err(format!("Unexpected argument: '{}'", text))
+        }
+    }
+
+    Ok(Some(options))
+}
 
 fn main() {
-    let secret = rand::thread_rng().gen_range(1, 101);
-
-    loop {
-        println!("Please input yout guess:");
-        
-        let mut guess = String::new();
-        io::stdin().read_line(&mut guess)
-            .expect("Failed to read the line!");
-    
-        let guess : u32 = match guess.trim().parse() {
-            Ok(num) => num,
-            Err(_) => continue
-        };
-    
-        match guess.cmp(&secret) {
-            Ordering::Less => println!("Too small!"),
-            Ordering::Greater => println!("Too big!"),
-            Ordering::Equal => {
-                println!("You guessed it!");
-                break;
-            }
+    let args : Vec<String> = env::args().skip(1).collect();
+
+    let options = match parse_args(&args) {
+        Ok(Some(val)) => val,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        },
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let mut rng = match options.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy()
+    };
+
+    if let Mode::Simulate{games} = options.mode {
+        let difficulties = options.difficulty.map_or(Difficulty::ALL.to_vec(), |difficulty| vec![difficulty]);
+
+        for difficulty in difficulties {
+            println!("{}", solver::simulate(difficulty, games, &mut rng));
         }
+
+        return;
     }
-}
\ No newline at end of file
+
+    let table = ScoreTable::load(&options.scores).unwrap_or_else(|e| {
+        eprintln!("[!!] Could not read the scores from {}: {}", options.scores.display(), e);
+        ScoreTable::default()
+    });
+
+    let stdin = io::stdin();
+    let mut session = Session::new(stdin.lock(), io::stdout(), rng);
+
+    session.difficulty = options.difficulty;
+    session.table = table;
+    session.scores = Some(options.scores);
+
+    let result = match options.mode {
+        Mode::Reverse => session.run_reverse(),
+        _ => session.run()
+    };
+
+    result.expect("Failed to read the line!");
+}
@@ -0,0 +1,151 @@
+use std::cmp::Reverse;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::difficulty::Difficulty;
+
+/// Best scores kept per difficulty
+const KEEP: usize = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Entry {
+    pub difficulty: Difficulty,
+    pub score: u32,
+    pub attempts: u32
+}
+
+/// The best scores of each difficulty, highest first. Stored as a text file
+/// with one `difficulty score attempts` line per entry.
+#[derive(Debug, Default, PartialEq)]
+pub struct ScoreTable {
+    entries: Vec<Entry>
+}
+
+impl ScoreTable {
+    /// Reads the table from `text`, skipping malformed lines
+    pub fn parse(text: &str) -> ScoreTable {
+        let mut table = ScoreTable::default();
+
+        for line in text.lines() {
+            let fields : Vec<&str> = line.split_whitespace().collect();
+
+            if let [difficulty, score, attempts] = fields.as_slice() {
+                if let (Some(difficulty), Ok(score), Ok(attempts)) = (Difficulty::parse(difficulty), score.parse(), attempts.parse()) {
+                    table.record(Entry{difficulty, score, attempts});
+                }
+            }
+        }
+
+        table
+    }
+
+    /// Reads the table from `path`, a missing file is an empty table
+    pub fn load(path: &Path) -> io::Result<ScoreTable> {
+        match fs::read_to_string(path) {
+            Ok(text) => Ok(ScoreTable::parse(&text)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(ScoreTable::default()),
+            Err(e) => Err(e)
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let lines : Vec<String> = self.entries.iter()
+            .map(|entry| format!("{} {} {}\n", entry.difficulty.name(), entry.score, entry.attempts))
+            .collect();
+
+        fs::write(path, lines.concat())
+    }
+
+    pub fn best(&self, difficulty: Difficulty) -> impl Iterator<Item = &Entry> {
+        self.entries.iter().filter(move |entry| entry.difficulty == difficulty)
+    }
+
+    /// Adds a win to the table, returns its 1-based rank within the
+    /// difficulty if it is among the best ones. Ties rank below the earlier
+    /// entries.
+    pub fn record(&mut self, entry: Entry) -> Option<usize> {
+        let rank = self.best(entry.difficulty).take_while(|other| other.score >= entry.score).count();
+
+        if rank >= KEEP {
+            return None;
+        }
+
+        self.entries.push(entry);
+
+        // Stable, so ties keep the earlier entries first
+        self.entries.sort_by_key(|other| (other.difficulty, Reverse(other.score)));
+
+        let mut kept = 0;
+
+        self.entries.retain(|other| {
+            if other.difficulty == entry.difficulty {
+                kept += 1;
+            }
+
+            other.difficulty != entry.difficulty || kept <= KEEP
+        });
+
+        Some(rank + 1)
+    }
+}
+
+/// Lists the best scores of every difficulty
+impl fmt::Display for ScoreTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for difficulty in Difficulty::ALL.iter() {
+            writeln!(f, "{}:", difficulty.name())?;
+
+            if self.best(*difficulty).next().is_none() {
+                writeln!(f, "  -")?;
+            }
+
+            for (rank, entry) in self.best(*difficulty).enumerate() {
+                writeln!(f, "  {}. {:>4} points in {} attempt(s)", rank + 1, entry.score, entry.attempts)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(difficulty: Difficulty, score: u32) -> Entry {
+        Entry{difficulty, score, attempts: 1}
+    }
+
+    #[test]
+    fn keeps_the_best_scores_per_difficulty() {
+        let mut table = ScoreTable::default();
+
+        for score in [50, 10, 70, 30, 20, 60].iter() {
+            table.record(entry(Difficulty::Easy, *score));
+        }
+
+        assert_eq!(table.record(entry(Difficulty::Hard, 5)), Some(1));
+        assert_eq!(table.record(entry(Difficulty::Easy, 40)), Some(4));
+        assert_eq!(table.record(entry(Difficulty::Easy, 1)), None);
+
+        let scores : Vec<u32> = table.best(Difficulty::Easy).map(|entry| entry.score).collect();
+        assert_eq!(scores, vec![70, 60, 50, 40, 30]);
+    }
+
+    #[test]
+    fn round_trips_through_text() {
+        let table = ScoreTable::parse("medium 200 1\nnot a line\nhard 40 10\nmedium 28 7\n");
+
+        assert_eq!(table.best(Difficulty::Medium).count(), 2);
+        assert_eq!(table.best(Difficulty::Hard).next(), Some(&Entry{difficulty: Difficulty::Hard, score: 40, attempts: 10}));
+
+        let path = std::env::temp_dir().join(format!("x01_scores_{}.txt", std::process::id()));
+        table.save(&path).unwrap();
+        assert_eq!(ScoreTable::load(&path).unwrap(), table);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(ScoreTable::load(Path::new("/nonexistent/scores.txt")).unwrap(), ScoreTable::default());
+    }
+}
@@ -0,0 +1,287 @@
+use std::cmp::Ordering;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+use rand::Rng;
+
+use crate::difficulty::Difficulty;
+use crate::game::{Game, State};
+use crate::scores::{Entry, ScoreTable};
+use crate::solver::Guesser;
+
+/// The interactive side of the game: reads guesses from `input`, writes the
+/// prompts and feedback to `output` and draws secrets from `rng`
+pub struct Session<I: BufRead, O: Write, R: Rng> {
+    input: I,
+    output: O,
+    rng: R,
+    /// Asked before every game if `None`
+    pub difficulty: Option<Difficulty>,
+    pub table: ScoreTable,
+    /// Where the table is saved after every new best score, nowhere if `None`
+    pub scores: Option<PathBuf>
+}
+
+impl<I: BufRead, O: Write, R: Rng> Session<I, O, R> {
+    pub fn new(input: I, output: O, rng: R) -> Session<I, O, R> {
+        Session{input, output, rng, difficulty: None, table: ScoreTable::default(), scores: None}
+    }
+
+    /// Reads a line, `None` at the end of the input
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        let mut line = String::new();
+
+        match self.input.read_line(&mut line)? {
+            0 => Ok(None),
+            _ => Ok(Some(line))
+        }
+    }
+
+    fn choose_difficulty(&mut self) -> io::Result<Option<Difficulty>> {
+        loop {
+            writeln!(self.output, "Choose a difficulty: [e]asy, [m]edium or [h]ard")?;
+
+            for difficulty in Difficulty::ALL.iter() {
+                writeln!(self.output, "  {}", difficulty)?;
+            }
+
+            match self.read_line()? {
+                Some(line) => if let Some(difficulty) = Difficulty::parse(&line) {
+                    return Ok(Some(difficulty));
+                },
+                None => return Ok(None)
+            }
+        }
+    }
+
+    /// Plays one game, `None` if the input ended before it did
+    fn play(&mut self, difficulty: Difficulty) -> io::Result<Option<Game>> {
+        let mut game = Game::new(difficulty, &mut self.rng);
+
+        while game.state() == State::Playing {
+            writeln!(self.output, "Please input your guess ({}/{}):", game.attempts() + 1, difficulty.max_attempts())?;
+
+            let line = match self.read_line()? {
+                Some(val) => val,
+                None => return Ok(None)
+            };
+
+            let guess : u32 = match line.trim().parse() {
+                Ok(num) => num,
+                Err(_) => continue
+            };
+
+            match game.guess(guess) {
+                Ok(feedback) => match feedback.ordering {
+                    Ordering::Less => writeln!(self.output, "Too small!")?,
+                    Ordering::Greater => writeln!(self.output, "Too big!")?,
+                    Ordering::Equal => ()
+                },
+                Err(e) => writeln!(self.output, "{}", e)?
+            }
+        }
+
+        Ok(Some(game))
+    }
+
+    /// Guesses the number the player thinks of, `false` if the input ended
+    /// before the number was found
+    fn guess_number(&mut self, difficulty: Difficulty) -> io::Result<bool> {
+        let range = difficulty.range();
+        let mut guesser = Guesser::new(range.clone());
+
+        writeln!(self.output, "Think of a number between {} and {}, I will guess it.", range.start(), range.end())?;
+
+        loop {
+            writeln!(self.output, "Is it {}? [h]igher, [l]ower or [c]orrect", guesser.guess())?;
+
+            let line = match self.read_line()? {
+                Some(val) => val.trim().to_lowercase(),
+                None => return Ok(false)
+            };
+
+            // How the guess compares to the number, like the feedback of a game
+            let ordering = match line.as_str() {
+                "h" | "higher" => Ordering::Less,
+                "l" | "lower" => Ordering::Greater,
+                "c" | "correct" => Ordering::Equal,
+                _ => continue
+            };
+
+            match guesser.answer(ordering) {
+                Ok(Some(number)) => {
+                    writeln!(self.output, "Got it, {} in {} guess(es)!", number, guesser.guesses())?;
+                    return Ok(true);
+                },
+                Ok(None) => (),
+                Err(e) => {
+                    writeln!(self.output, "{}.", e)?;
+                    return Ok(true);
+                }
+            }
+        }
+    }
+
+    fn play_again(&mut self) -> io::Result<bool> {
+        writeln!(self.output, "Play again? [y/n]")?;
+
+        let answer = self.read_line()?.map(|answer| answer.trim().to_lowercase());
+
+        Ok(matches!(answer.as_deref(), Some("y") | Some("yes")))
+    }
+
+    /// The difficulty of the next game, `None` if the input ended
+    fn difficulty(&mut self) -> io::Result<Option<Difficulty>> {
+        match self.difficulty {
+            Some(val) => Ok(Some(val)),
+            None => self.choose_difficulty()
+        }
+    }
+
+    /// Guesses the numbers the player thinks of until they do not want to
+    /// play anymore or the input ends
+    pub fn run_reverse(&mut self) -> io::Result<()> {
+        while let Some(difficulty) = self.difficulty()? {
+            if !self.guess_number(difficulty)? || !self.play_again()? {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Plays games until the player does not want to or the input ends
+    pub fn run(&mut self) -> io::Result<()> {
+        loop {
+            let difficulty = match self.difficulty()? {
+                Some(val) => val,
+                None => return Ok(())
+            };
+
+            let game = match self.play(difficulty)? {
+                Some(val) => val,
+                None => return Ok(())
+            };
+
+            let difficulty = game.difficulty();
+
+            if game.state() == State::Won {
+                let attempts = game.attempts();
+                let score = difficulty.score(attempts);
+
+                writeln!(self.output, "You guessed it in {} attempt(s)! Score: {}", attempts, score)?;
+
+                if let Some(rank) = self.table.record(Entry{difficulty, score, attempts}) {
+                    writeln!(self.output, "New best score, #{} on {}!", rank, difficulty.name())?;
+
+                    if let Some(path) = &self.scores {
+                        if let Err(e) = self.table.save(path) {
+                            eprintln!("[!!] Could not save the scores to {}: {}", path.display(), e);
+                        }
+                    }
+                }
+            } else {
+                writeln!(self.output, "Out of attempts! The number was {}.", game.secret())?;
+            }
+
+            writeln!(self.output, "\nBest scores:\n{}", self.table)?;
+
+            if !self.play_again()? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    /// Runs a session seeded with `seed` on the scripted `input`, returns
+    /// the output and the table
+    fn script(seed: u64, input: &str) -> (String, ScoreTable) {
+        let mut output = Vec::new();
+        let mut session = Session::new(input.as_bytes(), &mut output, StdRng::seed_from_u64(seed));
+
+        session.run().unwrap();
+
+        let table = session.table;
+        (String::from_utf8(output).unwrap(), table)
+    }
+
+    fn script_reverse(input: &str) -> String {
+        let mut output = Vec::new();
+        let mut session = Session::new(input.as_bytes(), &mut output, StdRng::seed_from_u64(0));
+
+        session.run_reverse().unwrap();
+
+        String::from_utf8(output).unwrap()
+    }
+
+    /// The secrets the session seeded with `seed` draws
+    fn secrets(seed: u64, difficulties: &[Difficulty]) -> Vec<u32> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        difficulties.iter().map(|difficulty| Game::new(*difficulty, &mut rng).secret()).collect()
+    }
+
+    #[test]
+    fn scripted_win_records_the_score() {
+        let secret = secrets(1, &[Difficulty::Easy])[0];
+        let (output, table) = script(1, &format!("e\n{}\n{}\nn\n", if secret == 1 { 2 } else { 1 }, secret));
+
+        assert!(output.contains("You guessed it in 2 attempt(s)! Score: 90"), "{}", output);
+        assert!(output.contains("New best score, #1 on easy!"));
+        assert_eq!(table.best(Difficulty::Easy).count(), 1);
+    }
+
+    #[test]
+    fn play_again_draws_a_new_secret() {
+        let secrets = secrets(9, &[Difficulty::Hard, Difficulty::Hard]);
+        let (output, table) = script(9, &format!("h\n{}\ny\nhard\n{}\nn\n", secrets[0], secrets[1]));
+
+        assert_eq!(output.matches("You guessed it in 1 attempt(s)! Score: 400").count(), 2, "{}", output);
+        assert_eq!(table.best(Difficulty::Hard).count(), 2);
+    }
+
+    #[test]
+    fn scripted_loss_reveals_the_secret() {
+        let secret = secrets(3, &[Difficulty::Medium])[0];
+        let wrong = if secret == 1 { 2 } else { 1 };
+        let guesses = format!("{}\n", wrong).repeat(7);
+        let (output, table) = script(3, &format!("x\nm\nabc\n500\n{}n\n", guesses));
+
+        assert!(output.contains("500 is not between 1 and 100"));
+        assert!(output.contains(&format!("Out of attempts! The number was {}.", secret)), "{}", output);
+        assert!(output.contains("Please input your guess (7/7):"));
+        assert_eq!(table, ScoreTable::default());
+    }
+
+    #[test]
+    fn ends_with_the_input() {
+        let secret = secrets(5, &[Difficulty::Medium])[0];
+        let (output, table) = script(5, &format!("m\n{}\n", if secret == 50 { 51 } else { 50 }));
+
+        assert!(output.ends_with("Please input your guess (2/7):\n"), "{}", output);
+        assert_eq!(table, ScoreTable::default());
+    }
+
+    #[test]
+    fn reverse_mode_finds_the_number() {
+        let output = script_reverse("m\nh\nl\nmaybe\nc\ny\ne\nc\nn\n");
+
+        assert!(output.contains("Think of a number between 1 and 100"));
+        assert!(output.contains("Is it 50? [h]igher") && output.contains("Is it 75?") && output.contains("Is it 62?"), "{}", output);
+        assert!(output.contains("Got it, 62 in 3 guess(es)!"));
+        assert!(output.contains("Got it, 25 in 1 guess(es)!"));
+    }
+
+    #[test]
+    fn reverse_mode_detects_contradictions() {
+        let output = script_reverse("e\nl\nl\nl\nl\nl\nn\n");
+
+        assert!(output.contains("The answers contradict each other, the number has to be between 1 and 2 so it cannot be lower than 1."), "{}", output);
+        assert!(output.ends_with("Play again? [y/n]\n"));
+    }
+}
@@ -0,0 +1,186 @@
+use std::cmp::Ordering;
+use std::error;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use rand::Rng;
+
+use crate::difficulty::Difficulty;
+use crate::game::{Game, State};
+
+/// The answers left no number to guess
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Contradiction {
+    pub guess: u32,
+    pub ordering: Ordering,
+    /// The numbers the earlier answers narrowed the secret down to
+    pub low: u32,
+    pub high: u32
+}
+
+impl fmt::Display for Contradiction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let direction = match self.ordering {
+            Ordering::Less => "higher",
+            _ => "lower"
+        };
+
+        write!(f, "The answers contradict each other, the number has to be between {} and {} so it cannot be {} than {}", self.low, self.high, direction, self.guess)
+    }
+}
+
+impl error::Error for Contradiction {}
+
+/// Guesses a secret by binary search, halving the numbers still possible
+/// with every answer
+#[derive(Clone, Debug)]
+pub struct Guesser {
+    low: u32,
+    high: u32,
+    guesses: u32
+}
+
+impl Guesser {
+    pub fn new(range: RangeInclusive<u32>) -> Guesser {
+        Guesser{low: *range.start(), high: *range.end(), guesses: 0}
+    }
+
+    /// The middle of the numbers still possible
+    pub fn guess(&self) -> u32 {
+        self.low + (self.high - self.low) / 2
+    }
+
+    /// Guesses answered so far
+    pub fn guesses(&self) -> u32 {
+        self.guesses
+    }
+
+    /// Narrows the numbers down with how the current guess compares to the
+    /// secret, `Less` meaning too small like in `Feedback`. Returns the secret
+    /// once it is found.
+    pub fn answer(&mut self, ordering: Ordering) -> Result<Option<u32>, Contradiction> {
+        let guess = self.guess();
+        let contradiction = Contradiction{guess, ordering, low: self.low, high: self.high};
+
+        match ordering {
+            Ordering::Equal => {
+                self.guesses += 1;
+                return Ok(Some(guess));
+            },
+            Ordering::Less if guess == self.high => return Err(contradiction),
+            Ordering::Greater if guess == self.low => return Err(contradiction),
+            Ordering::Less => self.low = guess + 1,
+            Ordering::Greater => self.high = guess - 1
+        }
+
+        self.guesses += 1;
+        Ok(None)
+    }
+}
+
+/// Plays `game` to the end with a `Guesser`
+pub fn solve(game: &mut Game) {
+    let mut guesser = Guesser::new(game.difficulty().range());
+
+    while game.state() == State::Playing {
+        let feedback = game.guess(guesser.guess()).expect("The guesser stays in the range");
+        guesser.answer(feedback.ordering).expect("The game answers consistently");
+    }
+}
+
+/// Outcome of many solved games
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stats {
+    pub difficulty: Difficulty,
+    pub games: u32,
+    pub won: u32,
+    /// Guesses over every game
+    pub guesses: u64,
+    pub worst: u32
+}
+
+impl Stats {
+    pub fn average(&self) -> f64 {
+        self.guesses as f64 / self.games as f64
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:<6} {} games, {} won, {:.3} guesses on average, {} at worst", self.difficulty.name(), self.games, self.won, self.average(), self.worst)
+    }
+}
+
+/// Solves `games` games with secrets drawn from `rng`
+pub fn simulate<R: Rng>(difficulty: Difficulty, games: u32, rng: &mut R) -> Stats {
+    let mut stats = Stats{difficulty, games, won: 0, guesses: 0, worst: 0};
+
+    for _ in 0..games {
+        let mut game = Game::new(difficulty, rng);
+        solve(&mut game);
+
+        if game.state() == State::Won {
+            stats.won += 1;
+        }
+
+        stats.guesses += u64::from(game.attempts());
+        stats.worst = stats.worst.max(game.attempts());
+    }
+
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn finds_every_secret_within_the_attempts() {
+        for difficulty in Difficulty::ALL.iter() {
+            for secret in difficulty.range() {
+                let mut game = Game::with_secret(*difficulty, secret);
+                solve(&mut game);
+
+                assert_eq!(game.state(), State::Won, "{} on {}", secret, difficulty.name());
+            }
+        }
+    }
+
+    #[test]
+    fn answers_narrow_the_guesses() {
+        let mut guesser = Guesser::new(1..=100);
+
+        assert_eq!(guesser.guess(), 50);
+        assert_eq!(guesser.answer(Ordering::Less), Ok(None));
+        assert_eq!(guesser.guess(), 75);
+        assert_eq!(guesser.answer(Ordering::Greater), Ok(None));
+        assert_eq!(guesser.guess(), 62);
+        assert_eq!(guesser.answer(Ordering::Equal), Ok(Some(62)));
+        assert_eq!(guesser.guesses(), 3);
+    }
+
+    #[test]
+    fn detects_contradicting_answers() {
+        let mut guesser = Guesser::new(1..=10);
+
+        assert_eq!(guesser.answer(Ordering::Less), Ok(None));
+        assert_eq!(guesser.answer(Ordering::Greater), Ok(None));
+        assert_eq!(guesser.guess(), 6);
+        assert_eq!(guesser.answer(Ordering::Greater), Err(Contradiction{guess: 6, ordering: Ordering::Greater, low: 6, high: 7}));
+        assert_eq!(guesser.guesses(), 2);
+
+        let error = Guesser::new(1..=1).answer(Ordering::Less).unwrap_err();
+        assert_eq!(error.to_string(), "The answers contradict each other, the number has to be between 1 and 1 so it cannot be higher than 1");
+    }
+
+    #[test]
+    fn simulations_average_the_guesses() {
+        let stats = simulate(Difficulty::Medium, 1000, &mut StdRng::seed_from_u64(1));
+
+        assert_eq!(stats.won, 1000);
+        assert!(stats.worst <= 7);
+        assert!(stats.average() > 5.0 && stats.average() < 6.5, "{}", stats);
+    }
+}
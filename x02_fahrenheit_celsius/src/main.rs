@@ -1,15 +1,271 @@
-fn celsius_to_fahrenheit(temp: f64) -> f64 {
-    temp * 1.8 + 32.0
+mod output;
+mod temperature;
+
+use std::env;
+use std::process;
+
+use output::Format;
+use temperature::{Scale, TemperatureError};
+
+const USAGE: &str = "Usage: x02_fahrenheit_celsius [VALUE SCALE] [OPTIONS]
+
+  VALUE SCALE           convert a single temperature, e.g. '100 F' or '-40 C'
+
+Options:
+  --from SCALE          scale of the table (default: C)
+  --to SCALE[,SCALE]    scales to convert to (default: F, C for a F table, or every other scale for a single value)
+  --start VALUE         first temperature of the table (default: -20)
+  --end VALUE           last temperature of the table (default: 20)
+  --step VALUE          distance between the rows of the table (default: 2)
+  --precision DIGITS    digits after the decimal point (default: 3)
+  --format FORMAT       text, csv, tsv, json or markdown (default: text)
+  -h, --help            print this help
+
+Scales: C (Celsius), F (Fahrenheit), K (Kelvin), R (Rankine), Re (Réaumur)";
+
+/// Tables longer than this are most likely a typo in the step
+const MAX_ROWS: usize = 10_000;
+const MAX_PRECISION: usize = 15;
+
+/// The temperatures to convert
+#[derive(Debug, PartialEq)]
+enum Values {
+    Single(f64),
+    Range{start: f64, end: f64, step: f64}
+}
+
+impl Values {
+    /// Computes every row as `start + i * step`, so rounding errors do not
+    /// pile up over the table
+    fn list(&self) -> Vec<f64> {
+        match *self {
+            Values::Single(value) => vec![value],
+            Values::Range{start, end, step} => {
+                let count = ((end - start) / step + 1e-9).floor() as usize + 1;
+                (0..count).map(|index| start + index as f64 * step).collect()
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    from: Scale,
+    to: Vec<Scale>,
+    values: Values,
+    precision: usize,
+    format: Format
+}
+
+fn parse_number(option: &str, text: Option<&String>) -> Result<f64, String> {
+    match text.map(|text| text.parse::<f64>()) {
+        Some(Ok(val)) if val.is_finite() => Ok(val),
+        Some(_) => Err(format!("'{}' expects a number", option)),
+        None => Err(format!("'{}' expects a value", option))
+    }
+}
+
+fn parse_scale(text: &str) -> Result<Scale, String> {
+    Scale::parse(text).ok_or_else(|| format!("Unknown scale: '{}'", text))
+}
+
+/// Reads the options from the command line arguments, `None` if the help
+/// was asked for
+fn parse_args(args: &[String]) -> Result<Option<Options>, String> {
+    let mut from = None;
+    let mut to = None;
+    let mut single = None;
+    let (mut start, mut end, mut step) = (None, None, None);
+    let mut precision = 3;
+    let mut format = Format::Text;
+
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--from" => from = Some(parse_scale(args.next().ok_or("'--from' expects a scale")?)?),
+            "--to" => {
+                let scales = args.next().ok_or("'--to' expects a scale")?;
+                to = Some(scales.split(',').map(parse_scale).collect::<Result<Vec<_>, _>>()?);
+            },
+            "--start" => start = Some(parse_number(arg, args.next())?),
+            "--end" => end = Some(parse_number(arg, args.next())?),
+            "--step" => step = Some(parse_number(arg, args.next())?),
+            "--precision" => {
+                precision = match args.next().map(|text| text.parse::<usize>()) {
+                    Some(Ok(val)) if val <= MAX_PRECISION => val,
+                    _ => return Err(format!("'--precision' expects a number of digits up to {}", MAX_PRECISION))
+                };
+            },
+            "--format" => {
+                let name = args.next().ok_or("'--format' expects a format")?;
+                format = Format::parse(name).ok_or_else(|| format!("Unknown format: '{}'", name))?;
+            },
+            text if single.is_none() && text.parse::<f64>().is_ok() => {
+                let value = parse_number("VALUE", Some(arg))?;
+                let scale = args.next().ok_or("A single temperature needs a scale, e.g. '100 F'")?;
+
+                single = Some(value);
+                from = Some(parse_scale(scale)?);
+            },
+            text => return Err(format!("Unexpected argument: '{}'", text))
+        }
+    }
+
+    let from = from.unwrap_or(Scale::Celsius);
+
+    let values = match single {
+        Some(_) if start.is_some() || end.is_some() || step.is_some() => {
+            return Err(String::from("A single temperature cannot be combined with '--start', '--end' or '--step'"));
+        },
+        Some(value) => Values::Single(value),
+        None => {
+            let (start, end, step) = (start.unwrap_or(-20.0), end.unwrap_or(20.0), step.unwrap_or(2.0));
+
+            if step <= 0.0 {
+                return Err(String::from("'--step' must be positive"));
+            }
+
+            if end < start {
+                return Err(format!("The table ends at {} before it starts at {}", end, start));
+            }
+
+            if (end - start) / step >= MAX_ROWS as f64 {
+                return Err(format!("The table would have more than {} rows, use a larger '--step'", MAX_ROWS));
+            }
+
+            Values::Range{start, end, step}
+        }
+    };
+
+    let to = match (to, &values) {
+        (Some(to), _) => {
+            // The source scale is always the first column, and columns must
+            // be unique to be keys of the JSON output
+            let mut unique : Vec<Scale> = Vec::new();
+
+            for scale in to {
+                if scale != from && !unique.contains(&scale) {
+                    unique.push(scale);
+                }
+            }
+
+            if unique.is_empty() {
+                return Err(format!("'--to' needs a scale other than the source scale, {}", from.name()));
+            }
+
+            unique
+        },
+        (None, Values::Single(_)) => Scale::ALL.iter().copied().filter(|scale| *scale != from).collect(),
+        (None, Values::Range{..}) if from == Scale::Fahrenheit => vec![Scale::Celsius],
+        (None, Values::Range{..}) => vec![Scale::Fahrenheit]
+    };
+
+    Ok(Some(Options{from, to, values, precision, format}))
+}
+
+/// One row per temperature, the source temperature first
+fn table(options: &Options) -> Result<Vec<Vec<f64>>, TemperatureError> {
+    options.values.list().into_iter()
+        .map(|value| {
+            let converted = options.to.iter().map(|to| temperature::convert(value, options.from, *to));
+            std::iter::once(temperature::convert(value, options.from, options.from)).chain(converted).collect()
+        })
+        .collect()
 }
 
 fn main() {
-    let mut celsius_degree : f64 = -20.0;
+    let args : Vec<String> = env::args().skip(1).collect();
 
-    println!("Celcius(°C)  Fahrenheit(°F)");
-    println!("===========================");
+    let options = match parse_args(&args) {
+        Ok(Some(val)) => val,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        },
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
 
-    while celsius_degree <= 20.0 {
-        println!("{:11.3} {:15.3}", celsius_degree, celsius_to_fahrenheit(celsius_degree));
-        celsius_degree += 2.0;
+    match table(&options) {
+        Ok(rows) => {
+            let columns : Vec<Scale> = std::iter::once(options.from).chain(options.to.iter().copied()).collect();
+            println!("{}", output::render(options.format, &columns, &rows, options.precision));
+        },
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Option<Options>, String> {
+        let args : Vec<String> = args.split_whitespace().map(String::from).collect();
+        parse_args(&args)
+    }
+
+    #[test]
+    fn defaults_to_the_celsius_table() {
+        let options = parse("").unwrap().unwrap();
+
+        assert_eq!(options, Options{from: Scale::Celsius, to: vec![Scale::Fahrenheit], values: Values::Range{start: -20.0, end: 20.0, step: 2.0}, precision: 3, format: Format::Text});
+        assert_eq!(options.values.list().len(), 21);
+    }
+
+    #[test]
+    fn single_temperatures_convert_to_every_other_scale() {
+        let options = parse("-40 F").unwrap().unwrap();
+
+        assert_eq!(options.to, vec![Scale::Celsius, Scale::Kelvin, Scale::Rankine, Scale::Reaumur]);
+        assert_eq!(table(&options).unwrap()[0][..2], [-40.0, -40.0]);
+        assert_eq!(parse("100 C --to K,F").unwrap().unwrap().to, vec![Scale::Kelvin, Scale::Fahrenheit]);
+        assert_eq!(parse("100 C --to C,F,F").unwrap().unwrap().to, vec![Scale::Fahrenheit]);
+        assert!(parse("--from C --to C").is_err());
+    }
+
+    #[test]
+    fn fahrenheit_tables_default_to_celsius() {
+        let options = parse("--from F --start 32 --end 32 --format json").unwrap().unwrap();
+        let columns : Vec<Scale> = std::iter::once(options.from).chain(options.to.iter().copied()).collect();
+
+        assert_eq!(options.to, vec![Scale::Celsius]);
+        assert_eq!(output::render(options.format, &columns, &table(&options).unwrap(), options.precision), "[\n  {\"fahrenheit\": 32.000, \"celsius\": 0.000}\n]");
+        assert_eq!(parse("--from K").unwrap().unwrap().to, vec![Scale::Fahrenheit]);
+    }
+
+    #[test]
+    fn ranges_do_not_accumulate_rounding_errors() {
+        let options = parse("--start 0 --end 1 --step 0.1").unwrap().unwrap();
+        let values = options.values.list();
+
+        assert_eq!(values.len(), 11);
+        assert_eq!(values[10], 1.0);
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        assert!(parse("--step 0").is_err());
+        assert!(parse("--start 5 --end 1").is_err());
+        assert!(parse("--step 0.0001").is_err());
+        assert!(parse("--precision 99").is_err());
+        assert!(parse("--from X").is_err());
+        assert!(parse("100").is_err());
+        assert!(parse("100 C --step 1").is_err());
+        assert!(parse("--format xml").is_err());
+        assert_eq!(parse("--help"), Ok(None));
+    }
+
+    #[test]
+    fn tables_reject_temperatures_below_absolute_zero() {
+        let options = parse("--from K --start -10 --end 10").unwrap().unwrap();
+
+        assert_eq!(table(&options), Err(TemperatureError::BelowAbsoluteZero{value: -10.0, scale: Scale::Kelvin}));
+    }
+}
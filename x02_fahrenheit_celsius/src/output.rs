@@ -0,0 +1,149 @@
+use crate::temperature::Scale;
+
+/// How the conversion table is printed
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    /// Right aligned columns for reading in a terminal
+    Text,
+    Csv,
+    Tsv,
+    /// An array with one object per row, keyed by the lowercase scale names
+    Json,
+    Markdown
+}
+
+impl Format {
+    pub fn parse(text: &str) -> Option<Format> {
+        match text.to_lowercase().as_str() {
+            "text" | "txt" => Some(Format::Text),
+            "csv" => Some(Format::Csv),
+            "tsv" => Some(Format::Tsv),
+            "json" => Some(Format::Json),
+            "markdown" | "md" => Some(Format::Markdown),
+            _ => None
+        }
+    }
+}
+
+fn header(scale: &Scale) -> String {
+    format!("{}({})", scale.name(), scale.symbol())
+}
+
+/// Quotes a CSV field if it contains a separator, a quote or a line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        String::from(field)
+    }
+}
+
+fn text(headers: &[String], cells: &[Vec<String>]) -> String {
+    let widths : Vec<usize> = headers.iter().enumerate()
+        .map(|(column, header)| cells.iter().map(|row| row[column].len()).chain(std::iter::once(header.chars().count())).max().unwrap_or(0))
+        .collect();
+
+    let align = |row: &[String]| -> String {
+        let row : Vec<String> = row.iter().zip(widths.iter()).map(|(cell, width)| format!("{:>1$}", cell, width)).collect();
+        row.join("  ")
+    };
+
+    let header = align(headers);
+    let mut lines = vec![header.clone(), "=".repeat(header.chars().count())];
+
+    lines.extend(cells.iter().map(|row| align(row)));
+    lines.join("\n")
+}
+
+fn separated(headers: &[String], cells: &[Vec<String>], separator: &str) -> String {
+    std::iter::once(headers).chain(cells.iter().map(|row| row.as_slice()))
+        .map(|row| row.join(separator))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn json(columns: &[Scale], cells: &[Vec<String>]) -> String {
+    if cells.is_empty() {
+        return String::from("[]");
+    }
+
+    let rows : Vec<String> = cells.iter()
+        .map(|row| {
+            let fields : Vec<String> = columns.iter().zip(row.iter())
+                .map(|(scale, cell)| format!("\"{}\": {}", scale.name().to_lowercase(), cell))
+                .collect();
+
+            format!("  {{{}}}", fields.join(", "))
+        })
+        .collect();
+
+    format!("[\n{}\n]", rows.join(",\n"))
+}
+
+fn markdown(headers: &[String], cells: &[Vec<String>]) -> String {
+    let line = |row: &[String]| format!("| {} |", row.join(" | "));
+
+    let mut lines = vec![line(headers), format!("|{}", "---:|".repeat(headers.len()))];
+
+    lines.extend(cells.iter().map(|row| line(row)));
+    lines.join("\n")
+}
+
+/// Renders the conversion table with one column per scale in `columns` and
+/// `precision` digits after the decimal point, without a trailing newline
+pub fn render(format: Format, columns: &[Scale], rows: &[Vec<f64>], precision: usize) -> String {
+    let headers : Vec<String> = columns.iter().map(header).collect();
+
+    let cells : Vec<Vec<String>> = rows.iter()
+        .map(|row| row.iter().map(|value| format!("{:.*}", precision, value)).collect())
+        .collect();
+
+    match format {
+        Format::Text => text(&headers, &cells),
+        Format::Csv => {
+            let headers : Vec<String> = headers.iter().map(|header| csv_field(header)).collect();
+            separated(&headers, &cells, ",")
+        },
+        Format::Tsv => separated(&headers, &cells, "\t"),
+        Format::Json => json(columns, &cells),
+        Format::Markdown => markdown(&headers, &cells)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render_table(format: Format) -> String {
+        render(format, &[Scale::Celsius, Scale::Fahrenheit], &[vec![-40.0, -40.0], vec![100.0, 212.0]], 1)
+    }
+
+    #[test]
+    fn text_is_right_aligned() {
+        assert_eq!(render_table(Format::Text), "Celsius(°C)  Fahrenheit(°F)\n===========================\n      -40.0           -40.0\n      100.0           212.0");
+    }
+
+    #[test]
+    fn separated_values() {
+        assert_eq!(render_table(Format::Csv), "Celsius(°C),Fahrenheit(°F)\n-40.0,-40.0\n100.0,212.0");
+        assert_eq!(render_table(Format::Tsv), "Celsius(°C)\tFahrenheit(°F)\n-40.0\t-40.0\n100.0\t212.0");
+        assert_eq!(csv_field("a,\"b\""), "\"a,\"\"b\"\"\"");
+    }
+
+    #[test]
+    fn json_has_an_object_per_row() {
+        assert_eq!(render_table(Format::Json), "[\n  {\"celsius\": -40.0, \"fahrenheit\": -40.0},\n  {\"celsius\": 100.0, \"fahrenheit\": 212.0}\n]");
+        assert_eq!(render(Format::Json, &[Scale::Kelvin], &[], 3), "[]");
+    }
+
+    #[test]
+    fn markdown_table() {
+        assert_eq!(render_table(Format::Markdown), "| Celsius(°C) | Fahrenheit(°F) |\n|---:|---:|\n| -40.0 | -40.0 |\n| 100.0 | 212.0 |");
+    }
+
+    #[test]
+    fn formats_parse_from_names() {
+        assert_eq!(Format::parse("MD"), Some(Format::Markdown));
+        assert_eq!(Format::parse("xml"), None);
+    }
+}
@@ -0,0 +1,171 @@
+use std::error;
+use std::fmt;
+
+/// Temperature scales, conversions between them go through Celsius
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Scale {
+    Celsius,
+    Fahrenheit,
+    Kelvin,
+    Rankine,
+    Reaumur
+}
+
+impl Scale {
+    pub const ALL: [Scale; 5] = [Scale::Celsius, Scale::Fahrenheit, Scale::Kelvin, Scale::Rankine, Scale::Reaumur];
+
+    /// Reads a scale from its name or symbol, ignoring case: `C`, `°F`,
+    /// `kelvin`, `Ré`, ...
+    pub fn parse(text: &str) -> Option<Scale> {
+        match text.to_lowercase().trim_start_matches('°') {
+            "c" | "celsius" => Some(Scale::Celsius),
+            "f" | "fahrenheit" => Some(Scale::Fahrenheit),
+            "k" | "kelvin" => Some(Scale::Kelvin),
+            "r" | "ra" | "rankine" => Some(Scale::Rankine),
+            "re" | "ré" | "reaumur" | "réaumur" => Some(Scale::Reaumur),
+            _ => None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Scale::Celsius => "Celsius",
+            Scale::Fahrenheit => "Fahrenheit",
+            Scale::Kelvin => "Kelvin",
+            Scale::Rankine => "Rankine",
+            Scale::Reaumur => "Réaumur"
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Scale::Celsius => "°C",
+            Scale::Fahrenheit => "°F",
+            Scale::Kelvin => "K",
+            Scale::Rankine => "°Ra",
+            Scale::Reaumur => "°Ré"
+        }
+    }
+
+    /// The lowest temperature on the scale, compared in the scale itself so
+    /// that e.g. -459.67 °F is accepted despite rounding
+    pub fn absolute_zero(self) -> f64 {
+        match self {
+            Scale::Celsius => -273.15,
+            Scale::Fahrenheit => -459.67,
+            Scale::Kelvin | Scale::Rankine => 0.0,
+            Scale::Reaumur => -218.52
+        }
+    }
+
+    fn to_celsius(self, value: f64) -> f64 {
+        match self {
+            Scale::Celsius => value,
+            Scale::Fahrenheit => (value - 32.0) * 5.0 / 9.0,
+            Scale::Kelvin => value - 273.15,
+            Scale::Rankine => (value - 491.67) * 5.0 / 9.0,
+            Scale::Reaumur => value * 5.0 / 4.0
+        }
+    }
+
+    fn celsius_to(self, value: f64) -> f64 {
+        match self {
+            Scale::Celsius => value,
+            Scale::Fahrenheit => value * 9.0 / 5.0 + 32.0,
+            Scale::Kelvin => value + 273.15,
+            Scale::Rankine => value * 9.0 / 5.0 + 491.67,
+            Scale::Reaumur => value * 4.0 / 5.0
+        }
+    }
+}
+
+impl fmt::Display for Scale {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TemperatureError {
+    BelowAbsoluteZero{value: f64, scale: Scale},
+    /// Infinities and NaN are not temperatures
+    NotFinite(f64)
+}
+
+impl fmt::Display for TemperatureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TemperatureError::BelowAbsoluteZero{value, scale} => write!(f, "{} {} is below absolute zero ({} {})", value, scale, scale.absolute_zero(), scale),
+            TemperatureError::NotFinite(value) => write!(f, "{} is not a temperature", value)
+        }
+    }
+}
+
+impl error::Error for TemperatureError {}
+
+/// Converts `value` from one scale to another, rejecting temperatures below
+/// absolute zero
+pub fn convert(value: f64, from: Scale, to: Scale) -> Result<f64, TemperatureError> {
+    if !value.is_finite() {
+        return Err(TemperatureError::NotFinite(value));
+    }
+
+    if value < from.absolute_zero() {
+        return Err(TemperatureError::BelowAbsoluteZero{value, scale: from});
+    }
+
+    if from == to {
+        return Ok(value);
+    }
+
+    Ok(to.celsius_to(from.to_celsius(value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(value: Result<f64, TemperatureError>, expected: f64) {
+        let value = value.unwrap();
+        assert!((value - expected).abs() < 1e-9, "{} != {}", value, expected);
+    }
+
+    #[test]
+    fn fixed_points() {
+        assert_close(convert(100.0, Scale::Celsius, Scale::Fahrenheit), 212.0);
+        assert_close(convert(-40.0, Scale::Fahrenheit, Scale::Celsius), -40.0);
+        assert_close(convert(0.0, Scale::Celsius, Scale::Kelvin), 273.15);
+        assert_close(convert(0.0, Scale::Kelvin, Scale::Rankine), 0.0);
+        assert_close(convert(80.0, Scale::Reaumur, Scale::Celsius), 100.0);
+        assert_close(convert(671.67, Scale::Rankine, Scale::Fahrenheit), 212.0);
+    }
+
+    #[test]
+    fn every_direction_round_trips() {
+        for from in Scale::ALL.iter() {
+            for to in Scale::ALL.iter() {
+                let there = convert(36.6 - from.absolute_zero(), *from, *to).unwrap();
+                assert_close(convert(there, *to, *from), 36.6 - from.absolute_zero());
+            }
+        }
+    }
+
+    #[test]
+    fn absolute_zero_is_the_limit() {
+        for scale in Scale::ALL.iter() {
+            assert_close(convert(scale.absolute_zero(), *scale, Scale::Kelvin), 0.0);
+        }
+
+        assert_eq!(convert(-300.0, Scale::Celsius, Scale::Kelvin), Err(TemperatureError::BelowAbsoluteZero{value: -300.0, scale: Scale::Celsius}));
+        assert_eq!(convert(-1.0, Scale::Rankine, Scale::Rankine), Err(TemperatureError::BelowAbsoluteZero{value: -1.0, scale: Scale::Rankine}));
+        assert!(convert(f64::NAN, Scale::Celsius, Scale::Kelvin).is_err());
+    }
+
+    #[test]
+    fn scales_parse_from_names_and_symbols() {
+        assert_eq!(Scale::parse("°F"), Some(Scale::Fahrenheit));
+        assert_eq!(Scale::parse("KELVIN"), Some(Scale::Kelvin));
+        assert_eq!(Scale::parse("Ré"), Some(Scale::Reaumur));
+        assert_eq!(Scale::parse("x"), None);
+    }
+}
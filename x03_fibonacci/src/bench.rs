@@ -0,0 +1,92 @@
+use std::hint;
+use std::time::{Duration, Instant};
+
+use crate::Algorithm;
+
+/// Every measurement repeats the computation for at least this long
+const MIN_TIME: Duration = Duration::from_millis(20);
+
+/// Mean time per call of each algorithm for one n, `None` if the algorithm
+/// does not support it
+pub struct Row {
+    pub n: u64,
+    pub times: Vec<(Algorithm, Option<Duration>)>
+}
+
+fn measure(algorithm: Algorithm, n: u64) -> Duration {
+    let start = Instant::now();
+    let mut calls = 0;
+
+    while calls == 0 || start.elapsed() < MIN_TIME {
+        hint::black_box(algorithm.compute(hint::black_box(n)));
+        calls += 1;
+    }
+
+    start.elapsed() / calls
+}
+
+pub fn run(algorithms: &[Algorithm], ns: &[u64]) -> Vec<Row> {
+    ns.iter()
+        .map(|n| Row{
+            n: *n,
+            times: algorithms.iter().map(|algorithm| (*algorithm, Some(*n).filter(|n| algorithm.supports(*n)).map(|n| measure(*algorithm, n)))).collect()
+        })
+        .collect()
+}
+
+/// Formats a duration with the largest unit that keeps it above 1
+fn human(duration: Duration) -> String {
+    let nanos = duration.as_nanos() as f64;
+
+    match nanos {
+        val if val < 1e3 => format!("{:.0} ns", val),
+        val if val < 1e6 => format!("{:.1} µs", val / 1e3),
+        val if val < 1e9 => format!("{:.1} ms", val / 1e6),
+        val => format!("{:.2} s", val / 1e9)
+    }
+}
+
+/// One line per n with the time per call of every algorithm, `-` where an
+/// algorithm does not support the n
+pub fn render(rows: &[Row]) -> String {
+    let algorithms : Vec<Algorithm> = match rows.first() {
+        Some(row) => row.times.iter().map(|(algorithm, _)| *algorithm).collect(),
+        None => return String::new()
+    };
+
+    let mut lines = Vec::with_capacity(rows.len() + 1);
+    let header : Vec<String> = algorithms.iter().map(|algorithm| format!("{:>15}", algorithm.name())).collect();
+
+    lines.push(format!("{:>10}{}", "n", header.join("")));
+
+    for row in rows {
+        let times : Vec<String> = row.times.iter()
+            .map(|(_, time)| format!("{:>15}", time.map(human).unwrap_or_else(|| String::from("-"))))
+            .collect();
+
+        lines.push(format!("{:>10}{}", row.n, times.join("")));
+    }
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsupported_n_are_skipped() {
+        let rows = run(&[Algorithm::Binet, Algorithm::Matrix], &[100]);
+
+        assert_eq!(rows[0].times[0].1, None);
+        assert!(rows[0].times[1].1.is_some());
+        assert!(render(&rows).contains(" -"));
+    }
+
+    #[test]
+    fn durations_are_human_readable() {
+        assert_eq!(human(Duration::from_nanos(850)), "850 ns");
+        assert_eq!(human(Duration::from_nanos(12_340)), "12.3 µs");
+        assert_eq!(human(Duration::from_millis(1500)), "1.50 s");
+    }
+}
@@ -0,0 +1,117 @@
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+
+/// Returns F(n) of the standard Fibonacci series, F(0) = 0 and F(1) = 1,
+/// using the identities
+///     F(2k)     = F(k) * (2 * F(k + 1) - F(k))
+///     F(2k + 1) = F(k)^2 + F(k + 1)^2
+/// on the bits of n from the most significant one down
+///
+/// Time Complexity: O(log n) big integer multiplications
+/// Space Complexity: O(n) bits for the result
+pub fn fast_doubling(n: u64) -> BigUint {
+    // F(k) and F(k + 1), k being the bits of n read so far
+    let mut a = BigUint::zero();
+    let mut b = BigUint::one();
+
+    for bit in (0..64 - n.leading_zeros()).rev() {
+        let even = &a * ((&b << 1usize) - &a);
+        let odd = &a * &a + &b * &b;
+
+        if (n >> bit) & 1 == 1 {
+            b = &even + &odd;
+            a = odd;
+        } else {
+            a = even;
+            b = odd;
+        }
+    }
+
+    a
+}
+
+/// 2x2 matrix in row-major order
+#[derive(Clone, Debug, PartialEq)]
+struct Matrix([BigUint; 4]);
+
+impl Matrix {
+    fn identity() -> Matrix {
+        Matrix([BigUint::one(), BigUint::zero(), BigUint::zero(), BigUint::one()])
+    }
+
+    fn mul(&self, other: &Matrix) -> Matrix {
+        let [a, b, c, d] = &self.0;
+        let [e, f, g, h] = &other.0;
+
+        Matrix([a * e + b * g, a * f + b * h, c * e + d * g, c * f + d * h])
+    }
+}
+
+/// Returns F(n) of the standard Fibonacci series, F(0) = 0 and F(1) = 1,
+/// as the top right element of
+///     | 1 1 |^n   | F(n + 1) F(n)     |
+///     | 1 0 |   = | F(n)     F(n - 1) |
+/// raised by repeated squaring
+///
+/// Time Complexity: O(log n) big integer multiplications
+/// Space Complexity: O(n) bits for the result
+pub fn matrix(n: u64) -> BigUint {
+    let mut result = Matrix::identity();
+    let mut base = Matrix([BigUint::one(), BigUint::one(), BigUint::one(), BigUint::zero()]);
+    let mut n = n;
+
+    while n > 0 {
+        if n & 1 == 1 {
+            result = result.mul(&base);
+        }
+
+        n >>= 1;
+
+        if n > 0 {
+            base = base.mul(&base);
+        }
+    }
+
+    let [_, fib, _, _] = result.0;
+    fib
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_values() {
+        let expected : Vec<u32> = vec![0, 1, 1, 2, 3, 5, 8, 13, 21, 34, 55];
+
+        for (n, val) in expected.iter().enumerate() {
+            assert_eq!(fast_doubling(n as u64), BigUint::from(*val));
+            assert_eq!(matrix(n as u64), BigUint::from(*val));
+        }
+    }
+
+    #[test]
+    fn past_u128() {
+        let expected : BigUint = "354224848179261915075".parse().unwrap();
+        assert_eq!(fast_doubling(100), expected);
+
+        let expected : BigUint = "222232244629420445529739893461909967206666939096499764990979600".parse().unwrap();
+        assert_eq!(matrix(300), expected);
+    }
+
+    #[test]
+    fn algorithms_agree() {
+        for n in (0..2000).step_by(37) {
+            assert_eq!(fast_doubling(n), matrix(n));
+        }
+    }
+
+    #[test]
+    fn one_millionth() {
+        let fib = fast_doubling(1_000_000).to_string();
+
+        assert_eq!(fib.len(), 208_988);
+        assert!(fib.starts_with("19532821287077577316"));
+        assert!(fib.ends_with("68996526838242546875"));
+    }
+}
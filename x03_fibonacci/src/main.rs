@@ -1,5 +1,16 @@
+mod bench;
+mod fast;
+mod sequence;
+
+use std::collections::HashMap;
+use std::env;
+use std::process;
 use std::vec;
 
+use num_bigint::BigUint;
+
+use sequence::Sequence;
+
 /// Returns nth element in fibonacci series
 /// Index starts at zero
 ///
@@ -30,17 +41,320 @@ fn fib2(n: u32) -> u128 {
     }
 
     match stack.pop() {
-        Some(val) => {
-            return val;
-        },
+        Some(val) => val,
         None => {
             println!("[!!] Stack is empty");
-            return 0;
+            0
         }
-    };
+    }
+}
+
+/// Returns nth element in fibonacci series
+/// Index starts at zero
+///
+/// Time Complexity: O(n)
+/// Space Complexity: O(n)
+fn fib_memo(n: u32) -> u128 {
+    fn memoized(n: u32, memo: &mut HashMap<u32, u128>) -> u128 {
+        if n <= 1 {
+            return 1;
+        }
+
+        if let Some(val) = memo.get(&n) {
+            return *val;
+        }
+
+        let val = memoized(n - 1, memo) + memoized(n - 2, memo);
+        memo.insert(n, val);
+        val
+    }
+
+    memoized(n, &mut HashMap::new())
+}
+
+/// Returns nth element in fibonacci series
+/// Index starts at zero
+///
+/// Time Complexity: O(n)
+/// Space Complexity: O(1)
+fn fib_const(n: u32) -> u128 {
+    let (mut prev, mut curr) = (1, 1);
+
+    for _ in 1..n {
+        let next = prev + curr;
+        prev = curr;
+        curr = next;
+    }
+
+    curr
+}
+
+/// Largest n that `fib_binet` gets exactly right, `f64` rounding errors
+/// exceed 0.5 past it
+const BINET_MAX_N: u32 = 74;
+
+/// Returns nth element in fibonacci series
+/// Index starts at zero
+///
+/// Rounds phi^(n + 1) / sqrt(5), see `BINET_MAX_N` for its range
+///
+/// Time Complexity: O(1)
+/// Space Complexity: O(1)
+fn fib_binet(n: u32) -> u128 {
+    let sqrt5 = 5f64.sqrt();
+    let phi = (1.0 + sqrt5) / 2.0;
+
+    (phi.powi(n as i32 + 1) / sqrt5).round() as u128
+}
+
+const USAGE: &str = "Usage: x03_fibonacci [N] [OPTIONS]
+
+  N                     print F(N) for N up to 10000000, where F(0) = 0 and F(1) = 1
+
+Options:
+  -a, --algorithm NAME  fast-doubling (default), matrix, binet, constant-space, iterative, memoized or recursive
+  -d, --digits          print the number of decimal digits instead of F(N)
+  -s, --sequence NAME   print the first N (default: 20) elements of fibonacci, lucas, tribonacci or pell
+  -m, --modulo M        print F(N) mod M, or the Nth element of '--sequence' mod M
+  -p, --pisano          also print the Pisano period of M, for M up to 10000000
+  -b, --bench START..END
+                        time every algorithm for N from START to END
+      --step STEP       distance between the N of '--bench' (default: a tenth of the range)
+  -h, --help            print this help
+
+Without N, prints the first elements of the series with both u128 approaches";
+
+/// Largest N of any algorithm, F(N) has about 0.2 * N digits and the time
+/// to print them grows faster than N
+const MAX_N: u64 = 10_000_000;
+
+/// Largest modulus of `--pisano`, finding the period walks up to 6m elements
+const PISANO_MAX_MODULUS: u64 = 10_000_000;
+
+/// The ways to compute F(n) from the command line
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Algorithm {
+    Recursive,
+    Memoized,
+    Iterative,
+    ConstantSpace,
+    Binet,
+    FastDoubling,
+    Matrix
+}
+
+impl Algorithm {
+    const ALL: [Algorithm; 7] = [Algorithm::Recursive, Algorithm::Memoized, Algorithm::Iterative, Algorithm::ConstantSpace,
+        Algorithm::Binet, Algorithm::FastDoubling, Algorithm::Matrix];
+
+    fn parse(name: &str) -> Option<Algorithm> {
+        Algorithm::ALL.iter().copied().find(|algorithm| algorithm.name() == name)
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Algorithm::Recursive => "recursive",
+            Algorithm::Memoized => "memoized",
+            Algorithm::Iterative => "iterative",
+            Algorithm::ConstantSpace => "constant-space",
+            Algorithm::Binet => "binet",
+            Algorithm::FastDoubling => "fast-doubling",
+            Algorithm::Matrix => "matrix"
+        }
+    }
+
+    /// Largest n the algorithm can compute, the recursive one gets too slow
+    /// past 40, the `u128` ones overflow past F(186) and the big integer ones
+    /// take seconds at `MAX_N`
+    fn max_n(self) -> u64 {
+        match self {
+            Algorithm::Recursive => 40,
+            Algorithm::Memoized | Algorithm::Iterative | Algorithm::ConstantSpace => 186,
+            Algorithm::Binet => BINET_MAX_N as u64 + 1,
+            Algorithm::FastDoubling | Algorithm::Matrix => MAX_N
+        }
+    }
+
+    fn supports(self, n: u64) -> bool {
+        n <= self.max_n()
+    }
+
+    /// F(n), the `u128` functions are shifted by one as they start the
+    /// series at 1
+    fn compute(self, n: u64) -> BigUint {
+        let shifted : fn(u32) -> u128 = match self {
+            Algorithm::FastDoubling => return fast::fast_doubling(n),
+            Algorithm::Matrix => return fast::matrix(n),
+            _ if n == 0 => return BigUint::from(0u32),
+            Algorithm::Recursive => fib,
+            Algorithm::Memoized => fib_memo,
+            Algorithm::Iterative => fib2,
+            Algorithm::ConstantSpace => fib_const,
+            Algorithm::Binet => fib_binet
+        };
+
+        BigUint::from(shifted(n as u32 - 1))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    n: Option<u64>,
+    algorithm: Algorithm,
+    digits: bool,
+    sequence: Option<Sequence>,
+    modulo: Option<u64>,
+    pisano: bool,
+    /// Range of n for `--bench` and the step between them
+    bench: Option<(u64, u64)>,
+    step: Option<u64>
+}
+
+/// Reads `START..END` of `--bench`
+fn parse_range(text: &str) -> Result<(u64, u64), String> {
+    let invalid = || format!("Expected a range like '10..100' for '--bench', got '{}'", text);
+
+    let (start, end) = text.split_once("..").ok_or_else(invalid)?;
+    let (start, end) = (start.parse::<u64>().map_err(|_| invalid())?, end.parse::<u64>().map_err(|_| invalid())?);
+
+    if end < start {
+        return Err(invalid());
+    }
+
+    Ok((start, end))
+}
+
+/// Reads the options from the command line arguments, `None` if the help
+/// was asked for
+fn parse_args(args: &[String]) -> Result<Option<Options>, String> {
+    let mut options = Options{n: None, algorithm: Algorithm::FastDoubling, digits: false, sequence: None, modulo: None, pisano: false, bench: None, step: None};
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-d" | "--digits" => options.digits = true,
+            "-a" | "--algorithm" => {
+                let name = args.next().ok_or("'--algorithm' expects a name")?;
+                options.algorithm = Algorithm::parse(name).ok_or_else(|| format!("Unknown algorithm: '{}'", name))?;
+            },
+            "-s" | "--sequence" => {
+                let name = args.next().ok_or("'--sequence' expects a name")?;
+                options.sequence = Some(Sequence::parse(name).ok_or_else(|| format!("Unknown sequence: '{}'", name))?);
+            },
+            "-m" | "--modulo" => {
+                options.modulo = match args.next().map(|text| text.parse::<u64>()) {
+                    Some(Ok(val)) if val > 0 => Some(val),
+                    _ => return Err(String::from("'--modulo' expects a positive integer"))
+                };
+            },
+            "-p" | "--pisano" => options.pisano = true,
+            "-b" | "--bench" => options.bench = Some(parse_range(args.next().ok_or("'--bench' expects a range")?)?),
+            "--step" => {
+                options.step = match args.next().map(|text| text.parse::<u64>()) {
+                    Some(Ok(val)) if val > 0 => Some(val),
+                    _ => return Err(String::from("'--step' expects a positive integer"))
+                };
+            },
+            text if options.n.is_none() => {
+                options.n = Some(text.parse().map_err(|_| format!("Expected N to be a non-negative integer, got '{}'", text))?);
+            },
+            text => return Err(format!("Unexpected argument: '{}'", text))
+        }
+    }
+
+    if let (Some(n), None) = (options.n, options.modulo) {
+        let max = options.algorithm.max_n();
+
+        if n > max && options.sequence.is_none() {
+            let hint = if max < MAX_N { ", try '--algorithm fast-doubling'" } else { "" };
+            return Err(format!("The {} algorithm only supports N up to {}{}", options.algorithm.name(), max, hint));
+        }
+    }
+
+    if let Some((_, end)) = options.bench {
+        if end > MAX_N {
+            return Err(format!("'--bench' only supports N up to {}", MAX_N));
+        }
+    }
+
+    if options.modulo.is_some() && options.n.is_none() {
+        return Err(String::from("'--modulo' needs N"));
+    }
+
+    if options.pisano {
+        match options.modulo {
+            None => return Err(String::from("'--pisano' needs '--modulo'")),
+            Some(_) if options.sequence.is_some() => return Err(String::from("'--pisano' only applies to the fibonacci series")),
+            Some(m) if m > PISANO_MAX_MODULUS => return Err(format!("'--pisano' only supports M up to {}", PISANO_MAX_MODULUS)),
+            Some(_) => ()
+        }
+    }
+
+    Ok(Some(options))
 }
 
 fn main() {
+    let args : Vec<String> = env::args().skip(1).collect();
+
+    let options = match parse_args(&args) {
+        Ok(Some(val)) => val,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        },
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Some((start, end)) = options.bench {
+        let step = options.step.unwrap_or_else(|| ((end - start) / 10).max(1));
+        let ns : Vec<u64> = (start..=end).step_by(step as usize).collect();
+
+        println!("{}", bench::render(&bench::run(&Algorithm::ALL, &ns)));
+
+        return;
+    }
+
+    if let (Some(n), Some(m)) = (options.n, options.modulo) {
+        let result = match options.sequence {
+            Some(sequence) => sequence.iter().nth_mod(n, m).map(|val| val.to_string()),
+            None if options.pisano => sequence::fib_mod(n, m).and_then(|val| Ok(format!("{} (Pisano period {})", val, sequence::pisano_period(m)?))),
+            None => sequence::fib_mod(n, m).map(|val| val.to_string())
+        };
+
+        match result {
+            Ok(val) => println!("{}", val),
+            Err(e) => {
+                eprintln!("error: {}", e);
+                process::exit(1);
+            }
+        }
+
+        return;
+    }
+
+    if let Some(sequence) = options.sequence {
+        let elements : Vec<String> = sequence.iter().take(options.n.unwrap_or(20) as usize).map(|val| val.to_string()).collect();
+        println!("{}", elements.join(" "));
+
+        return;
+    }
+
+    if let Some(n) = options.n {
+        let fib = options.algorithm.compute(n).to_string();
+
+        if options.digits {
+            println!("{}", fib.len());
+        } else {
+            println!("{}", fib);
+        }
+
+        return;
+    }
+
     print!("Using recursive approach: ");
 
     for n in 0..=20 {
@@ -53,5 +367,62 @@ fn main() {
         print!("{} ", fib2(n));
     }
 
-    println!("");
-}
\ No newline at end of file
+    println!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Option<Options>, String> {
+        let args : Vec<String> = args.split_whitespace().map(String::from).collect();
+        parse_args(&args)
+    }
+
+    /// Checks every algorithm against fast doubling on every n it supports,
+    /// only up to 30 for the exponential one to keep the tests fast
+    #[test]
+    fn algorithms_agree_on_every_supported_n() {
+        for algorithm in Algorithm::ALL.iter() {
+            let max = match algorithm {
+                Algorithm::Recursive => 30,
+                _ => algorithm.max_n().min(1000)
+            };
+
+            for n in 0..=max {
+                assert_eq!(algorithm.compute(n), fast::fast_doubling(n), "{} n = {}", algorithm.name(), n);
+            }
+        }
+    }
+
+    #[test]
+    fn binet_is_exact_only_up_to_its_limit() {
+        assert_eq!(fib_binet(BINET_MAX_N), fib_const(BINET_MAX_N));
+        assert_ne!(fib_binet(BINET_MAX_N + 1), fib_const(BINET_MAX_N + 1));
+    }
+
+    #[test]
+    fn arguments() {
+        assert_eq!(parse("1000000 -a matrix -d"), Ok(Some(Options{n: Some(1_000_000), algorithm: Algorithm::Matrix, digits: true, sequence: None, modulo: None, pisano: false, bench: None, step: None})));
+        assert_eq!(parse(""), Ok(Some(Options{n: None, algorithm: Algorithm::FastDoubling, digits: false, sequence: None, modulo: None, pisano: false, bench: None, step: None})));
+        assert_eq!(parse("10 -s pell -m 7"), Ok(Some(Options{n: Some(10), algorithm: Algorithm::FastDoubling, digits: false, sequence: Some(Sequence::Pell), modulo: Some(7), pisano: false, bench: None, step: None})));
+        assert_eq!(parse("187 --algorithm constant-space"), Err(String::from("The constant-space algorithm only supports N up to 186, try '--algorithm fast-doubling'")));
+        assert_eq!(parse("18446744073709551615 -d"), Err(String::from("The fast-doubling algorithm only supports N up to 10000000")));
+        assert!(parse("-1").is_err());
+        assert!(parse("5 6").is_err());
+        assert!(parse("-a slow").is_err());
+        assert!(parse("-m 10").is_err());
+        assert!(parse("5 -m 0").is_err());
+        assert!(parse("-s padovan").is_err());
+        assert!(parse("10 -m 1000 --pisano").unwrap().unwrap().pisano);
+        assert!(parse("10 -p").is_err());
+        assert!(parse("10 -s lucas -m 7 -p").is_err());
+        assert!(parse("10 -m 10000001 -p").is_err());
+        assert_eq!(parse("-b 10..90 --step 20").unwrap().unwrap().bench, Some((10, 90)));
+        assert!(parse("-b 90..10").is_err());
+        assert!(parse("-b 10").is_err());
+        assert!(parse("10000001 -a matrix").is_err());
+        assert!(parse("-b 0..18446744073709551615").is_err());
+        assert!(parse("18446744073709551615 -m 1000000007").is_ok());
+    }
+}
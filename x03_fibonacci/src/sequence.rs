@@ -0,0 +1,251 @@
+use std::collections::VecDeque;
+use std::error;
+use std::fmt;
+
+use num_bigint::BigInt;
+use num_traits::{ToPrimitive, Zero};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SequenceError {
+    /// A recurrence needs as many seeds as coefficients, and at least one
+    SeedMismatch{coefficients: usize, seeds: usize},
+    ZeroModulus
+}
+
+impl fmt::Display for SequenceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SequenceError::SeedMismatch{coefficients, seeds} => write!(f, "A recurrence with {} coefficient(s) needs as many seeds, got {}", coefficients, seeds),
+            SequenceError::ZeroModulus => write!(f, "The modulus must be positive")
+        }
+    }
+}
+
+impl error::Error for SequenceError {}
+
+/// Lazy iterator over the linear recurrence
+///     a(n) = c[0] * a(n - 1) + c[1] * a(n - 2) + ... + c[k - 1] * a(n - k)
+/// starting with the seeds a(0), ..., a(k - 1), over big integers so that
+/// it never overflows
+#[derive(Clone, Debug)]
+pub struct LinearRecurrence {
+    coefficients: Vec<BigInt>,
+    /// The next k elements, a(n) first
+    window: VecDeque<BigInt>
+}
+
+impl LinearRecurrence {
+    pub fn new(coefficients: &[i64], seeds: &[i64]) -> Result<LinearRecurrence, SequenceError> {
+        if coefficients.is_empty() || coefficients.len() != seeds.len() {
+            return Err(SequenceError::SeedMismatch{coefficients: coefficients.len(), seeds: seeds.len()});
+        }
+
+        Ok(LinearRecurrence{
+            coefficients: coefficients.iter().map(|val| BigInt::from(*val)).collect(),
+            window: seeds.iter().map(|val| BigInt::from(*val)).collect()
+        })
+    }
+
+    /// The element `n` places after the current one modulo `m`, in the range
+    /// `0..m`. Raises the companion matrix of the recurrence to the nth power
+    /// by repeated squaring, so it takes O(k^3 log n) for k coefficients.
+    pub fn nth_mod(&self, n: u64, m: u64) -> Result<u64, SequenceError> {
+        if m == 0 {
+            return Err(SequenceError::ZeroModulus);
+        }
+
+        let reduce = |val: &BigInt| {
+            let modulus = BigInt::from(m);
+            (((val % &modulus) + &modulus) % &modulus).to_u64().unwrap_or(0)
+        };
+
+        let k = self.coefficients.len();
+
+        // Maps (a(i + k - 1), ..., a(i)) to (a(i + k), ..., a(i + 1))
+        let mut companion = vec![vec![0; k]; k];
+        companion[0] = self.coefficients.iter().map(reduce).collect();
+
+        for row in 1..k {
+            companion[row][row - 1] = 1 % m;
+        }
+
+        let power = matrix_pow(companion, n, m);
+        let state : Vec<u64> = self.window.iter().rev().map(reduce).collect();
+
+        // a(n) is the last element of power * state
+        Ok(power[k - 1].iter().zip(state.iter()).fold(0, |sum, (coefficient, val)| add_mod(sum, mul_mod(*coefficient, *val, m), m)))
+    }
+}
+
+impl Iterator for LinearRecurrence {
+    type Item = BigInt;
+
+    fn next(&mut self) -> Option<BigInt> {
+        let next = self.coefficients.iter().zip(self.window.iter().rev())
+            .fold(BigInt::zero(), |sum, (coefficient, val)| sum + coefficient * val);
+
+        self.window.push_back(next);
+        self.window.pop_front()
+    }
+}
+
+fn add_mod(a: u64, b: u64, m: u64) -> u64 {
+    ((a as u128 + b as u128) % m as u128) as u64
+}
+
+fn mul_mod(a: u64, b: u64, m: u64) -> u64 {
+    ((a as u128 * b as u128) % m as u128) as u64
+}
+
+fn matrix_mul(a: &[Vec<u64>], b: &[Vec<u64>], m: u64) -> Vec<Vec<u64>> {
+    let k = a.len();
+
+    (0..k).map(|row| (0..k).map(|col| (0..k).fold(0, |sum, i| add_mod(sum, mul_mod(a[row][i], b[i][col], m), m))).collect()).collect()
+}
+
+fn matrix_pow(base: Vec<Vec<u64>>, n: u64, m: u64) -> Vec<Vec<u64>> {
+    let k = base.len();
+    let mut result : Vec<Vec<u64>> = (0..k).map(|row| (0..k).map(|col| if row == col { 1 % m } else { 0 }).collect()).collect();
+    let mut base = base;
+    let mut n = n;
+
+    while n > 0 {
+        if n & 1 == 1 {
+            result = matrix_mul(&result, &base, m);
+        }
+
+        n >>= 1;
+
+        if n > 0 {
+            base = matrix_mul(&base, &base, m);
+        }
+    }
+
+    result
+}
+
+/// The well-known recurrences
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sequence {
+    /// 0, 1, 1, 2, 3, 5, ...
+    Fibonacci,
+    /// 2, 1, 3, 4, 7, 11, ...
+    Lucas,
+    /// 0, 0, 1, 1, 2, 4, 7, 13, ...
+    Tribonacci,
+    /// 0, 1, 2, 5, 12, 29, ...
+    Pell
+}
+
+impl Sequence {
+    pub fn parse(name: &str) -> Option<Sequence> {
+        match name {
+            "fibonacci" => Some(Sequence::Fibonacci),
+            "lucas" => Some(Sequence::Lucas),
+            "tribonacci" => Some(Sequence::Tribonacci),
+            "pell" => Some(Sequence::Pell),
+            _ => None
+        }
+    }
+
+    pub fn iter(self) -> LinearRecurrence {
+        let (coefficients, seeds) : (&[i64], &[i64]) = match self {
+            Sequence::Fibonacci => (&[1, 1], &[0, 1]),
+            Sequence::Lucas => (&[1, 1], &[2, 1]),
+            Sequence::Tribonacci => (&[1, 1, 1], &[0, 0, 1]),
+            Sequence::Pell => (&[2, 1], &[0, 1])
+        };
+
+        LinearRecurrence::new(coefficients, seeds).expect("built-in sequences are valid")
+    }
+}
+
+/// F(n) mod m, with F(0) = 0 and F(1) = 1
+pub fn fib_mod(n: u64, m: u64) -> Result<u64, SequenceError> {
+    Sequence::Fibonacci.iter().nth_mod(n, m)
+}
+
+/// Period of the Fibonacci series modulo m, which is at most 6m. Walks the
+/// series until it is back at 0, 1, so it takes O(m) steps.
+pub fn pisano_period(m: u64) -> Result<u64, SequenceError> {
+    if m == 0 {
+        return Err(SequenceError::ZeroModulus);
+    }
+
+    if m == 1 {
+        return Ok(1);
+    }
+
+    let (mut a, mut b) = (0, 1);
+    let mut period = 0;
+
+    loop {
+        let next = add_mod(a, b, m);
+        a = b;
+        b = next;
+        period += 1;
+
+        if a == 0 && b == 1 {
+            return Ok(period);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn first(sequence: Sequence, count: usize) -> Vec<i64> {
+        sequence.iter().take(count).map(|val| val.to_i64().unwrap()).collect()
+    }
+
+    #[test]
+    fn named_sequences() {
+        assert_eq!(first(Sequence::Fibonacci, 10), vec![0, 1, 1, 2, 3, 5, 8, 13, 21, 34]);
+        assert_eq!(first(Sequence::Lucas, 8), vec![2, 1, 3, 4, 7, 11, 18, 29]);
+        assert_eq!(first(Sequence::Tribonacci, 10), vec![0, 0, 1, 1, 2, 4, 7, 13, 24, 44]);
+        assert_eq!(first(Sequence::Pell, 8), vec![0, 1, 2, 5, 12, 29, 70, 169]);
+    }
+
+    #[test]
+    fn custom_recurrences() {
+        // Jacobsthal numbers, a(n) = a(n - 1) + 2 a(n - 2)
+        let jacobsthal : Vec<i64> = LinearRecurrence::new(&[1, 2], &[0, 1]).unwrap().take(7).map(|val| val.to_i64().unwrap()).collect();
+        assert_eq!(jacobsthal, vec![0, 1, 1, 3, 5, 11, 21]);
+
+        // Alternating signs, a(n) = -a(n - 1)
+        let signs : Vec<i64> = LinearRecurrence::new(&[-1], &[3]).unwrap().take(4).map(|val| val.to_i64().unwrap()).collect();
+        assert_eq!(signs, vec![3, -3, 3, -3]);
+
+        assert_eq!(LinearRecurrence::new(&[1, 1], &[0]).err(), Some(SequenceError::SeedMismatch{coefficients: 2, seeds: 1}));
+        assert!(LinearRecurrence::new(&[], &[]).is_err());
+    }
+
+    #[test]
+    fn modular_elements_match_the_iterators() {
+        for sequence in [Sequence::Fibonacci, Sequence::Lucas, Sequence::Tribonacci, Sequence::Pell].iter() {
+            for (n, val) in sequence.iter().take(200).enumerate() {
+                let expected = (val % BigInt::from(1_000_007)).to_u64().unwrap();
+                assert_eq!(sequence.iter().nth_mod(n as u64, 1_000_007), Ok(expected), "{:?} n = {}", sequence, n);
+            }
+        }
+
+        assert_eq!(LinearRecurrence::new(&[-1], &[3]).unwrap().nth_mod(1, 10), Ok(7));
+    }
+
+    #[test]
+    fn huge_indices_modulo() {
+        assert_eq!(fib_mod(10_u64.pow(18), 1_000_000_007), Ok(209_783_453));
+        assert_eq!(fib_mod(5, 1), Ok(0));
+        assert_eq!(fib_mod(5, 0), Err(SequenceError::ZeroModulus));
+    }
+
+    #[test]
+    fn pisano_periods() {
+        let periods : Vec<u64> = (1..=10).map(|m| pisano_period(m).unwrap()).collect();
+
+        assert_eq!(periods, vec![1, 3, 8, 6, 20, 24, 16, 12, 24, 60]);
+        assert_eq!(pisano_period(1000), Ok(1500));
+        assert_eq!(pisano_period(0), Err(SequenceError::ZeroModulus));
+    }
+}
@@ -0,0 +1,65 @@
+/// A built-in function, `min_args..=max_args` is the accepted argument count
+/// where a `max_args` of `None` means the function is variadic
+pub struct Builtin {
+    pub name: &'static str,
+    pub min_args: usize,
+    pub max_args: Option<usize>,
+    /// Infinite results from finite arguments are poles like `ln(0)`, not
+    /// overflows like `exp(1000)`
+    pub poles: bool,
+    pub apply: fn(&[f64]) -> f64
+}
+
+impl Builtin {
+    pub fn accepts(&self, count: usize) -> bool {
+        match self.max_args {
+            Some(max) => self.min_args <= count && count <= max,
+            None => self.min_args <= count
+        }
+    }
+
+    /// Human-readable argument count, e.g. "1", "1 or 2", "at least 1"
+    pub fn arity(&self) -> String {
+        match self.max_args {
+            Some(max) if max == self.min_args => format!("{}", max),
+            Some(max) if max == self.min_args + 1 => format!("{} or {}", self.min_args, max),
+            Some(max) => format!("{} to {}", self.min_args, max),
+            None => format!("at least {}", self.min_args)
+        }
+    }
+}
+
+pub static BUILTINS: &[Builtin] = &[
+    Builtin{name: "float", min_args: 1, max_args: Some(1), poles: false, apply: |args| args[0]},
+    Builtin{name: "sqrt", min_args: 1, max_args: Some(1), poles: false, apply: |args| args[0].sqrt()},
+    Builtin{name: "cbrt", min_args: 1, max_args: Some(1), poles: false, apply: |args| args[0].cbrt()},
+    Builtin{name: "abs", min_args: 1, max_args: Some(1), poles: false, apply: |args| args[0].abs()},
+    Builtin{name: "sin", min_args: 1, max_args: Some(1), poles: false, apply: |args| args[0].sin()},
+    Builtin{name: "cos", min_args: 1, max_args: Some(1), poles: false, apply: |args| args[0].cos()},
+    Builtin{name: "tan", min_args: 1, max_args: Some(1), poles: true, apply: |args| args[0].tan()},
+    Builtin{name: "asin", min_args: 1, max_args: Some(1), poles: false, apply: |args| args[0].asin()},
+    Builtin{name: "acos", min_args: 1, max_args: Some(1), poles: false, apply: |args| args[0].acos()},
+    Builtin{name: "atan", min_args: 1, max_args: Some(1), poles: false, apply: |args| args[0].atan()},
+    Builtin{name: "atan2", min_args: 2, max_args: Some(2), poles: false, apply: |args| args[0].atan2(args[1])},
+    Builtin{name: "sinh", min_args: 1, max_args: Some(1), poles: false, apply: |args| args[0].sinh()},
+    Builtin{name: "cosh", min_args: 1, max_args: Some(1), poles: false, apply: |args| args[0].cosh()},
+    Builtin{name: "tanh", min_args: 1, max_args: Some(1), poles: false, apply: |args| args[0].tanh()},
+    Builtin{name: "exp", min_args: 1, max_args: Some(1), poles: false, apply: |args| args[0].exp()},
+    Builtin{name: "ln", min_args: 1, max_args: Some(1), poles: true, apply: |args| args[0].ln()},
+    Builtin{name: "log", min_args: 1, max_args: Some(2), poles: true, apply: |args| match args.get(1) {
+        Some(base) => args[0].log(*base),
+        None => args[0].log10()
+    }},
+    Builtin{name: "log2", min_args: 1, max_args: Some(1), poles: true, apply: |args| args[0].log2()},
+    Builtin{name: "floor", min_args: 1, max_args: Some(1), poles: false, apply: |args| args[0].floor()},
+    Builtin{name: "ceil", min_args: 1, max_args: Some(1), poles: false, apply: |args| args[0].ceil()},
+    Builtin{name: "round", min_args: 1, max_args: Some(1), poles: false, apply: |args| args[0].round()},
+    Builtin{name: "trunc", min_args: 1, max_args: Some(1), poles: false, apply: |args| args[0].trunc()},
+    Builtin{name: "hypot", min_args: 2, max_args: Some(2), poles: false, apply: |args| args[0].hypot(args[1])},
+    Builtin{name: "min", min_args: 1, max_args: None, poles: false, apply: |args| args.iter().cloned().fold(f64::INFINITY, f64::min)},
+    Builtin{name: "max", min_args: 1, max_args: None, poles: false, apply: |args| args.iter().cloned().fold(f64::NEG_INFINITY, f64::max)}
+];
+
+pub fn lookup(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|builtin| builtin.name == name)
+}
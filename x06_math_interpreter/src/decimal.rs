@@ -0,0 +1,146 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{Pow, Signed, Zero};
+
+pub const DEFAULT_SCALE: u32 = 2;
+pub const MAX_SCALE: u32 = 40;
+
+/// How a decimal result with more digits than its scale is rounded
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rounding {
+    /// Ties go to the even neighbour, `0.125 -> 0.12`, also known as banker's rounding
+    HalfEven,
+    /// Ties go away from zero, `0.125 -> 0.13` and `-0.125 -> -0.13`
+    HalfUp,
+    /// Extra digits are dropped, `0.129 -> 0.12`
+    Truncate
+}
+
+impl Rounding {
+    pub fn parse(name: &str) -> Option<Rounding> {
+        match name {
+            "half-even" => Some(Rounding::HalfEven),
+            "half-up" => Some(Rounding::HalfUp),
+            "truncate" => Some(Rounding::Truncate),
+            _ => None
+        }
+    }
+
+    /// `numer / denom` rounded to an integer, `denom` must be positive
+    fn apply(self, numer: &BigInt, denom: &BigInt) -> BigInt {
+        let (quotient, remainder) = numer.div_rem(denom);
+
+        if remainder.is_zero() || self == Rounding::Truncate {
+            return quotient;
+        }
+
+        let away = &quotient + numer.signum();
+        let doubled : BigInt = remainder.abs() * 2;
+
+        match doubled.cmp(denom) {
+            Ordering::Less => quotient,
+            Ordering::Greater => away,
+            Ordering::Equal if self == Rounding::HalfUp || quotient.is_odd() => away,
+            Ordering::Equal => quotient
+        }
+    }
+}
+
+impl fmt::Display for Rounding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rounding::HalfEven => write!(f, "half-even"),
+            Rounding::HalfUp => write!(f, "half-up"),
+            Rounding::Truncate => write!(f, "truncate")
+        }
+    }
+}
+
+/// Base-10 fixed-point number `units / 10^scale`, every result is rounded
+/// back to `scale` digits after the decimal point with `rounding`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Decimal {
+    units: BigInt,
+    scale: u32,
+    rounding: Rounding
+}
+
+impl Decimal {
+    pub fn from_rational(val: &BigRational, scale: u32, rounding: Rounding) -> Decimal {
+        let numer = val.numer() * Pow::pow(BigInt::from(10), scale);
+
+        Decimal{units: rounding.apply(&numer, val.denom()), scale, rounding}
+    }
+
+    pub fn to_rational(&self) -> BigRational {
+        BigRational::new(self.units.clone(), Pow::pow(BigInt::from(10), self.scale))
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    pub fn rounding(&self) -> Rounding {
+        self.rounding
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.units.is_zero()
+    }
+
+    pub fn neg(self) -> Decimal {
+        Decimal{units: -self.units, ..self}
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.units.is_negative() { "-" } else { "" };
+        let digits = format!("{:0>width$}", self.units.abs(), width = self.scale as usize + 1);
+        let (integral, fraction) = digits.split_at(digits.len() - self.scale as usize);
+
+        if fraction.is_empty() {
+            write!(f, "{}{}", sign, integral)
+        } else {
+            write!(f, "{}{}.{}", sign, integral, fraction)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(numer: i64, denom: i64, scale: u32, rounding: Rounding) -> String {
+        let val = BigRational::new(BigInt::from(numer), BigInt::from(denom));
+        Decimal::from_rational(&val, scale, rounding).to_string()
+    }
+
+    #[test]
+    fn rounds_ties_by_rounding_mode() {
+        assert_eq!(decimal(125, 1000, 2, Rounding::HalfEven), "0.12");
+        assert_eq!(decimal(135, 1000, 2, Rounding::HalfEven), "0.14");
+        assert_eq!(decimal(125, 1000, 2, Rounding::HalfUp), "0.13");
+        assert_eq!(decimal(-125, 1000, 2, Rounding::HalfUp), "-0.13");
+        assert_eq!(decimal(129, 1000, 2, Rounding::Truncate), "0.12");
+        assert_eq!(decimal(-129, 1000, 2, Rounding::Truncate), "-0.12");
+    }
+
+    #[test]
+    fn rounds_non_ties_to_the_nearest() {
+        assert_eq!(decimal(2, 3, 2, Rounding::HalfEven), "0.67");
+        assert_eq!(decimal(-2, 3, 2, Rounding::HalfUp), "-0.67");
+        assert_eq!(decimal(1, 3, 0, Rounding::HalfEven), "0");
+    }
+
+    #[test]
+    fn pads_to_the_scale() {
+        assert_eq!(decimal(3, 1, 2, Rounding::HalfEven), "3.00");
+        assert_eq!(decimal(-1, 20, 3, Rounding::HalfEven), "-0.050");
+        assert_eq!(decimal(1234, 1, 0, Rounding::HalfEven), "1234");
+    }
+}
@@ -0,0 +1,85 @@
+use std::fmt;
+
+use colored::*;
+
+use crate::parser::Span;
+
+/// An error that can point at a part of the source line
+pub trait Diagnostic: fmt::Display {
+    /// `None` for errors that are not tied to a part of the source
+    fn span(&self) -> Option<Span>;
+
+    /// Optional hint on how to fix the error
+    fn help(&self) -> Option<&'static str> {
+        None
+    }
+}
+
+/// Renders `error` as `kind: message`, followed by the source line with the
+/// error span underlined, if there is one, and the help note, if there is one:
+///
+/// ```text
+/// LexerError: Unexpected char: '$' at position 4.
+///   | 2 + $
+///   |     ^
+///   = help: ...
+/// ```
+pub fn render<E: Diagnostic>(kind: &str, error: &E, source: &str) -> String {
+    let source = source.trim_end();
+    let mut result = format!("{}: {}", kind.red(), error);
+
+    if let Some(span) = error.span() {
+        let start = source.get(..span.start).unwrap_or(source).chars().count();
+        let width = match source.get(span.start..span.end) {
+            Some(text) if !text.is_empty() => text.chars().count(),
+            _ => 1
+        };
+
+        result += &format!("\n  {} {}", "|".blue(), source);
+        result += &format!("\n  {} {}{}", "|".blue(), " ".repeat(start), "^".repeat(width).red().bold());
+    }
+
+    if let Some(help) = error.help() {
+        result += &format!("\n  {} {}", "= help:".cyan(), help);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Lexer, Parser};
+
+    fn plain<E: Diagnostic>(kind: &str, error: &E, source: &str) -> String {
+        colored::control::set_override(false);
+        render(kind, error, source)
+    }
+
+    #[test]
+    fn underlines_the_error_span() {
+        let source = "2 + 3 $ 4\n";
+        let error = Lexer::lex(source).unwrap_err();
+
+        assert_eq!(plain("LexerError", &error, source),
+            "LexerError: Unexpected char: '$' at position 6.\n  | 2 + 3 $ 4\n  |       ^\n  = help: supported operators are + - * / ^ ** & | ~ << >> xor = and ,");
+    }
+
+    #[test]
+    fn underlines_multi_character_spans_with_help() {
+        let source = "1 + 1.2.3";
+        let error = Lexer::lex(source).unwrap_err();
+
+        assert_eq!(plain("LexerError", &error, source),
+            "LexerError: Malformed number literal: '1.2.' at position 4.\n  | 1 + 1.2.3\n  |     ^^^^\n  = help: a number literal can have at most one decimal point");
+    }
+
+    #[test]
+    fn points_past_the_end_on_missing_input() {
+        let source = "(1 + 2";
+        let errors = Parser::new(Lexer::lex(source).unwrap()).parse().unwrap_err();
+
+        assert_eq!(plain("ParserError", &errors[0], source),
+            "ParserError: Unclosed '(' at position 0, was expecting RPAREN.\n  | (1 + 2\n  |       ^\n  = help: did you forget a closing ')'?");
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::error::EvalError;
+use crate::format::Format;
+use crate::parser::ASTNode;
+use crate::value::{Mode, Value};
+
+pub const DEFAULT_MAX_DEPTH: usize = 200;
+pub const MAX_DEPTH_LIMIT: usize = 1000;
+
+/// Outcome of a single REPL input, kept so that later inputs can refer to it
+pub enum HistoryEntry {
+    Value(Value),
+    Definition,
+    Dump,
+    Error
+}
+
+/// A function defined in the REPL with `def`, `source` is the definition
+/// as it was typed in
+pub struct UserFunction {
+    pub params: Vec<String>,
+    pub body: ASTNode,
+    pub source: String
+}
+
+/// Variable bindings, user functions and output history that live for the
+/// whole REPL session
+pub struct Environment {
+    variables: HashMap<String, Value>,
+    functions: HashMap<String, Rc<UserFunction>>,
+    frames: Vec<HashMap<String, Value>>,
+    history: Vec<HistoryEntry>,
+    max_depth: usize,
+    mode: Mode,
+    format: Format
+}
+
+impl Default for Environment {
+    fn default() -> Environment {
+        Environment::new()
+    }
+}
+
+impl Environment {
+    pub fn new() -> Environment {
+        Environment{
+            variables: HashMap::new(),
+            functions: HashMap::new(),
+            frames: Vec::new(),
+            history: Vec::new(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            mode: Mode::Float,
+            format: Format::default()
+        }
+    }
+
+    /// Resolves `name` in the innermost call frame first, then in the globals
+    pub fn get(&self, name: &str) -> Option<Value> {
+        if let Some(frame) = self.frames.last() {
+            if let Some(val) = frame.get(name) {
+                return Some(val.clone());
+            }
+        }
+
+        self.variables.get(name).cloned()
+    }
+
+    /// Names of the global variables
+    pub fn variable_names(&self) -> HashSet<String> {
+        self.variables.keys().cloned().collect()
+    }
+
+    pub fn set(&mut self, name: &str, value: Value) {
+        self.variables.insert(String::from(name), value);
+    }
+
+    pub fn define(&mut self, name: &str, function: UserFunction) {
+        self.functions.insert(String::from(name), Rc::new(function));
+    }
+
+    pub fn function(&self, name: &str) -> Option<Rc<UserFunction>> {
+        self.functions.get(name).cloned()
+    }
+
+    /// User functions sorted by name
+    pub fn functions(&self) -> Vec<Rc<UserFunction>> {
+        let mut names : Vec<&String> = self.functions.keys().collect();
+        names.sort();
+
+        names.into_iter().map(|name| self.functions[name].clone()).collect()
+    }
+
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    pub fn set_max_depth(&mut self, depth: usize) -> Result<(), String> {
+        if depth == 0 || depth > MAX_DEPTH_LIMIT {
+            return Err(format!("Recursion depth must be between 1 and {}", MAX_DEPTH_LIMIT));
+        }
+
+        self.max_depth = depth;
+        Ok(())
+    }
+
+    /// Number type that literals are read as
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: Mode) {
+        self.mode = mode;
+    }
+
+    /// How outputs are rendered unless an input has its own `as` suffix
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    pub fn set_format(&mut self, format: Format) {
+        self.format = format;
+    }
+
+    /// Opens a call frame holding the arguments of a user function call
+    pub fn enter(&mut self, name: &str, frame: HashMap<String, Value>) -> Result<(), EvalError> {
+        if self.frames.len() >= self.max_depth {
+            return Err(EvalError::RecursionLimit{name: String::from(name), depth: self.max_depth});
+        }
+
+        self.frames.push(frame);
+        Ok(())
+    }
+
+    pub fn leave(&mut self) {
+        self.frames.pop();
+    }
+
+    /// Records the outcome of the next input, `Out[n]` is the nth recorded entry
+    pub fn record(&mut self, entry: HistoryEntry) {
+        self.history.push(entry);
+    }
+
+    /// Looks up `Out[index]`, or the most recent output if `index` is `None`
+    pub fn history(&self, index: Option<u32>) -> Result<Value, EvalError> {
+        let index = match index {
+            Some(val) => val as usize,
+            None => self.history.len()
+        };
+
+        if index == 0 || index > self.history.len() {
+            return Err(EvalError::MissingHistory(index));
+        }
+
+        match &self.history[index - 1] {
+            HistoryEntry::Value(val) => Ok(val.clone()),
+            HistoryEntry::Definition => Err(EvalError::NoValueInHistory{index, reason: "a definition"}),
+            HistoryEntry::Dump => Err(EvalError::NoValueInHistory{index, reason: "a dump"}),
+            HistoryEntry::Error => Err(EvalError::NoValueInHistory{index, reason: "an error"})
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use std::error;
+use std::fmt;
+
+use crate::diagnostics::Diagnostic;
+use crate::parser::{Lexeme, Span};
+
+/// Everything that can go wrong while evaluating a parsed statement
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalError {
+    /// `span` is the division or power that divided by zero
+    DivisionByZero{span: Span},
+    /// `span` is the subexpression whose result does not fit the number type
+    Overflow{span: Span},
+    /// A bitwise operator was applied to a value with a fractional part
+    NotAnInteger{span: Span},
+    NegativeShift{span: Span},
+    /// `+` or `-` between quantities of different dimensions, e.g. `3 m + 2 s`
+    DimensionMismatch{lhs: String, rhs: String, span: Span},
+    /// An absolute temperature like `20 degC` used in a way that depends on its scale
+    OffsetArithmetic{span: Span},
+    BelowAbsoluteZero{span: Span},
+    /// A quantity raised to a fractional power, e.g. `(2 m)^0.5`
+    FractionalPower{span: Span},
+    /// A quantity passed to a math function, `span` is the argument
+    DimensionedArgument{name: String, span: Span},
+    InvalidUnit{unit: String, span: Span},
+    /// A math function was called outside of its domain, e.g. `sqrt(-1)`
+    Domain(String),
+    InvalidLiteral(String),
+    UnknownIdentifier(String),
+    UnknownFunction(String),
+    ArityMismatch{name: String, expected: String, got: usize},
+    DuplicateParameter{name: String, param: String},
+    BuiltinRedefinition(String),
+    RecursionLimit{name: String, depth: usize},
+    MissingHistory(usize),
+    /// `Out[n]` exists but holds no value, `reason` says what it was instead
+    NoValueInHistory{index: usize, reason: &'static str},
+    MissingOperand(String),
+    UnexpectedNode(String)
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EvalError::DivisionByZero{..} => write!(f, "Division by zero"),
+            EvalError::Overflow{..} => write!(f, "Arithmetic overflow"),
+            EvalError::NotAnInteger{..} => write!(f, "Bitwise operators only accept integers"),
+            EvalError::NegativeShift{..} => write!(f, "Shift amount cannot be negative"),
+            EvalError::DimensionMismatch{lhs, rhs, ..} => write!(f, "Incompatible dimensions: {} and {}", lhs, rhs),
+            EvalError::OffsetArithmetic{..} => write!(f, "Ambiguous arithmetic on an absolute temperature"),
+            EvalError::BelowAbsoluteZero{..} => write!(f, "Temperature below absolute zero"),
+            EvalError::FractionalPower{..} => write!(f, "Quantities can only be raised to integer powers"),
+            EvalError::DimensionedArgument{name, ..} => write!(f, "Function '{}' only accepts dimensionless arguments", name),
+            EvalError::InvalidUnit{unit, ..} => write!(f, "Invalid unit: '{}'", unit),
+            EvalError::Domain(name) => write!(f, "Argument out of the domain of '{}'", name),
+            EvalError::InvalidLiteral(text) => write!(f, "Invalid number literal: '{}'", text),
+            EvalError::UnknownIdentifier(name) => write!(f, "Unknown identifier: '{}'", name),
+            EvalError::UnknownFunction(name) => write!(f, "Unknown function: '{}'", name),
+            EvalError::ArityMismatch{name, expected, got} => write!(f, "Function '{}' expects {} argument(s), got {}", name, expected, got),
+            EvalError::DuplicateParameter{name, param} => write!(f, "Duplicate parameter '{}' in definition of '{}'", param, name),
+            EvalError::BuiltinRedefinition(name) => write!(f, "Cannot redefine built-in function '{}'", name),
+            EvalError::RecursionLimit{name, depth} => write!(f, "Maximum recursion depth of {} exceeded in '{}'", depth, name),
+            EvalError::MissingHistory(index) => write!(f, "Out[{}] does not exist", index),
+            EvalError::NoValueInHistory{index, reason} => write!(f, "Out[{}] was {} and has no value", index, reason),
+            EvalError::MissingOperand(node) => write!(f, "Missing operand on: {}", node),
+            EvalError::UnexpectedNode(node) => write!(f, "Unexpected node in AST: {}", node)
+        }
+    }
+}
+
+impl EvalError {
+    /// Moves the span of the error, if it has one, to `span`
+    pub fn relocate(self, span: Span) -> EvalError {
+        match self {
+            EvalError::DivisionByZero{..} => EvalError::DivisionByZero{span},
+            EvalError::Overflow{..} => EvalError::Overflow{span},
+            EvalError::NotAnInteger{..} => EvalError::NotAnInteger{span},
+            EvalError::NegativeShift{..} => EvalError::NegativeShift{span},
+            EvalError::DimensionMismatch{lhs, rhs, ..} => EvalError::DimensionMismatch{lhs, rhs, span},
+            EvalError::OffsetArithmetic{..} => EvalError::OffsetArithmetic{span},
+            EvalError::BelowAbsoluteZero{..} => EvalError::BelowAbsoluteZero{span},
+            EvalError::FractionalPower{..} => EvalError::FractionalPower{span},
+            EvalError::DimensionedArgument{name, ..} => EvalError::DimensionedArgument{name, span},
+            EvalError::InvalidUnit{unit, ..} => EvalError::InvalidUnit{unit, span},
+            error => error
+        }
+    }
+}
+
+impl Diagnostic for EvalError {
+    fn span(&self) -> Option<Span> {
+        match self {
+            EvalError::DivisionByZero{span} => Some(*span),
+            EvalError::Overflow{span} => Some(*span),
+            EvalError::NotAnInteger{span} => Some(*span),
+            EvalError::NegativeShift{span} => Some(*span),
+            EvalError::DimensionMismatch{span, ..} => Some(*span),
+            EvalError::OffsetArithmetic{span} => Some(*span),
+            EvalError::BelowAbsoluteZero{span} => Some(*span),
+            EvalError::FractionalPower{span} => Some(*span),
+            EvalError::DimensionedArgument{span, ..} => Some(*span),
+            EvalError::InvalidUnit{span, ..} => Some(*span),
+            _ => None
+        }
+    }
+
+    fn help(&self) -> Option<&'static str> {
+        match self {
+            EvalError::Overflow{..} => Some("use ':mode exact' for arbitrarily large integers"),
+            EvalError::NotAnInteger{..} => Some("use floor(x), round(x) or trunc(x) to drop the fraction"),
+            EvalError::OffsetArithmetic{..} => Some("use 'deltaC' or 'deltaF' for temperature differences, or convert to K"),
+            EvalError::DimensionedArgument{..} => Some("divide by a unit to get a plain number, e.g. 'sqrt(x / 1 m^2)'"),
+            EvalError::InvalidUnit{..} => Some("offset units like 'degC' cannot be combined with other units"),
+            _ => None
+        }
+    }
+}
+
+impl error::Error for EvalError {}
+
+/// Errors found while splitting the source into tokens
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexError {
+    UnexpectedChar{ch: char, span: Span},
+    MalformedNumber{text: String, span: Span},
+    /// An `e`/`E` that is not followed by any exponent digits, e.g. `1e`
+    MissingExponent{text: String, span: Span},
+    InvalidHistoryReference{text: String, span: Span},
+    MalformedHistoryReference{span: Span},
+    /// A `)` without a matching `(` before it
+    UnbalancedParen{span: Span},
+    /// A fractional or exponent literal in the integer mode
+    NotAnInteger{text: String, span: Span},
+    /// An integer literal that does not fit in an `i128`
+    IntegerOverflow{text: String, span: Span}
+}
+
+impl Diagnostic for LexError {
+    fn span(&self) -> Option<Span> {
+        match self {
+            LexError::UnexpectedChar{span, ..} => Some(*span),
+            LexError::MalformedNumber{span, ..} => Some(*span),
+            LexError::MissingExponent{span, ..} => Some(*span),
+            LexError::InvalidHistoryReference{span, ..} => Some(*span),
+            LexError::MalformedHistoryReference{span} => Some(*span),
+            LexError::UnbalancedParen{span} => Some(*span),
+            LexError::NotAnInteger{span, ..} => Some(*span),
+            LexError::IntegerOverflow{span, ..} => Some(*span)
+        }
+    }
+
+    fn help(&self) -> Option<&'static str> {
+        match self {
+            LexError::UnexpectedChar{..} => Some("supported operators are + - * / ^ ** & | ~ << >> xor = and ,"),
+            LexError::MalformedNumber{..} => Some("a number literal can have at most one decimal point"),
+            LexError::MissingExponent{..} => Some("add digits after the exponent, e.g. '1e3' or '1e-3'"),
+            LexError::InvalidHistoryReference{..} => Some("the output index is too large"),
+            LexError::MalformedHistoryReference{..} => Some("refer to outputs like 'Out[3]'"),
+            LexError::UnbalancedParen{..} => Some("did you forget an opening '('?"),
+            LexError::NotAnInteger{..} => Some("the integer mode only reads whole numbers, use ':mode float' for fractions"),
+            LexError::IntegerOverflow{..} => Some("integers must fit in 128 bits, use ':mode exact' for larger ones")
+        }
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LexError::UnexpectedChar{ch, span} => write!(f, "Unexpected char: '{0}' at position {1}.", ch, span.start),
+            LexError::MalformedNumber{text, span} => write!(f, "Malformed number literal: '{0}' at position {1}.", text, span.start),
+            LexError::MissingExponent{text, span} => write!(f, "Malformed number literal: '{0}' at position {1}, exponent has no digits.", text, span.start),
+            LexError::InvalidHistoryReference{text, span} => write!(f, "Invalid history reference: '{0}' at position {1}.", text, span.start),
+            LexError::MalformedHistoryReference{span} => write!(f, "Malformed history reference, expected 'Out[<n>]' at position {}.", span.start),
+            LexError::UnbalancedParen{span} => write!(f, "Unbalanced ')' at position {}.", span.start),
+            LexError::NotAnInteger{text, span} => write!(f, "Non-integer literal: '{0}' at position {1}.", text, span.start),
+            LexError::IntegerOverflow{text, span} => write!(f, "Integer literal too large: '{0}' at position {1}.", text, span.start)
+        }
+    }
+}
+
+impl error::Error for LexError {}
+
+/// Errors found while building the AST from the tokens
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    UnexpectedToken{found: Lexeme, expected: String, span: Span},
+    UnexpectedEnd{expected: String, span: Span},
+    /// `open` is the span of the `(` that was never closed
+    UnclosedParen{open: Span, span: Span},
+    InvalidParameter{found: Lexeme, span: Span}
+}
+
+impl Diagnostic for ParseError {
+    fn span(&self) -> Option<Span> {
+        match self {
+            ParseError::UnexpectedToken{span, ..} => Some(*span),
+            ParseError::UnexpectedEnd{span, ..} => Some(*span),
+            ParseError::UnclosedParen{span, ..} => Some(*span),
+            ParseError::InvalidParameter{span, ..} => Some(*span)
+        }
+    }
+
+    fn help(&self) -> Option<&'static str> {
+        match self {
+            ParseError::UnexpectedToken{found: Lexeme::Rparen(_), ..} => Some("did you forget an operand before the ')'?"),
+            ParseError::UnexpectedToken{..} => None,
+            ParseError::UnexpectedEnd{..} => Some("the expression is incomplete"),
+            ParseError::UnclosedParen{..} => Some("did you forget a closing ')'?"),
+            ParseError::InvalidParameter{..} => Some("parameters must be plain names, e.g. 'def f(a, b) = a + b'")
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::UnexpectedToken{found, expected, ..} => write!(f, "Unexpected token: {}, was expecting {}.", found, expected),
+            ParseError::UnexpectedEnd{expected, ..} => write!(f, "Unexpected end of input, was expecting {}.", expected),
+            ParseError::UnclosedParen{open, ..} => write!(f, "Unclosed '(' at position {}, was expecting RPAREN.", open.start),
+            ParseError::InvalidParameter{found, ..} => write!(f, "Unexpected parameter: {}, was expecting IDENT.", found)
+        }
+    }
+}
+
+impl error::Error for ParseError {}
@@ -0,0 +1,349 @@
+use std::fmt;
+
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{Pow, Signed, Zero};
+
+use crate::decimal::{self, Decimal, Rounding};
+use crate::value::Value;
+
+/// Significant digits shown for exact values that do not terminate, e.g. `1/3 as sci`
+const MAX_SIGNIFICANT: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Radix {
+    Dec,
+    Hex,
+    Oct,
+    Bin
+}
+
+impl Radix {
+    fn base(self) -> u32 {
+        match self {
+            Radix::Dec => 10,
+            Radix::Hex => 16,
+            Radix::Oct => 8,
+            Radix::Bin => 2
+        }
+    }
+
+    /// Same prefixes the lexer reads, so outputs can be pasted back in
+    fn prefix(self) -> &'static str {
+        match self {
+            Radix::Dec => "",
+            Radix::Hex => "0x",
+            Radix::Oct => "0o",
+            Radix::Bin => "0b"
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Notation {
+    Plain,
+    /// One digit before the decimal point, e.g. `1.5e4`
+    Scientific,
+    /// Exponents that are multiples of 3, e.g. `15e3`
+    Engineering
+}
+
+/// How `Out[n]` values are rendered, set for the session with `:fmt` or for
+/// a single input with an `as` suffix. Values that are not integers are
+/// always rendered in decimal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Format {
+    pub radix: Radix,
+    /// Digits after the decimal point, `None` shows as many as needed
+    pub precision: Option<usize>,
+    pub notation: Notation,
+    /// Groups the integer digits by 3 with `,` in decimal, and by 4 with `_`
+    /// in the other radixes
+    pub separators: bool
+}
+
+impl Default for Format {
+    fn default() -> Format {
+        Format{radix: Radix::Dec, precision: None, notation: Notation::Plain, separators: false}
+    }
+}
+
+/// `10^exponent` for any sign of `exponent`
+fn pow10(exponent: i64) -> BigRational {
+    let power = BigRational::from_integer(Pow::pow(BigInt::from(10), exponent.unsigned_abs()));
+
+    if exponent < 0 {
+        power.recip()
+    } else {
+        power
+    }
+}
+
+/// `floor(log10(val))` for a positive `val`
+fn floor_log10(val: &BigRational) -> i64 {
+    let mut exponent = val.numer().to_string().len() as i64 - val.denom().to_string().len() as i64;
+
+    while *val >= pow10(exponent + 1) {
+        exponent += 1;
+    }
+
+    while *val < pow10(exponent) {
+        exponent -= 1;
+    }
+
+    exponent
+}
+
+/// Significant digits needed to show `val` exactly, at most `MAX_SIGNIFICANT`.
+/// Floats use the shortest representation that reads back as the same float.
+fn significant_digits(value: &Value, val: &BigRational) -> usize {
+    if let Value::Float(val) = value {
+        let shortest = format!("{:e}", val.abs());
+        return shortest.split('e').next().unwrap_or("").chars().filter(|c| c.is_ascii_digit()).count();
+    }
+
+    let mantissa = val.abs() * pow10(-floor_log10(&val.abs()));
+
+    (0..MAX_SIGNIFICANT).find(|places| (&mantissa * pow10(*places as i64)).is_integer()).unwrap_or(MAX_SIGNIFICANT - 1) + 1
+}
+
+/// Renders `val` as `mantissa` `e` `exponent`, where the exponent is a
+/// multiple of `step` and the mantissa has `precision` decimals or enough
+/// for `significant` digits
+fn scientific(val: &BigRational, step: i64, precision: Option<usize>, significant: usize) -> String {
+    if val.is_zero() {
+        return format!("{:.*}e0", precision.unwrap_or(0), 0.0);
+    }
+
+    let sign = if val.is_negative() { "-" } else { "" };
+    let val = val.abs();
+    let mut exponent = floor_log10(&val);
+
+    loop {
+        let shifted = exponent - exponent.rem_euclid(step);
+        let integral_digits = (exponent - shifted + 1) as usize;
+        let places = precision.unwrap_or_else(|| significant.saturating_sub(integral_digits));
+        let mantissa = Decimal::from_rational(&(&val * pow10(-shifted)), places as u32, Rounding::HalfEven);
+
+        // Rounding can carry into a new digit, e.g. 9.99 to 10.0
+        if mantissa.to_rational() >= pow10(integral_digits as i64) {
+            exponent += 1;
+            continue;
+        }
+
+        return format!("{}{}e{}", sign, mantissa, shifted);
+    }
+}
+
+/// Puts `separator` between every `size` digits of the leading digits in `text`
+fn group(text: &str, size: usize, separator: char) -> String {
+    let start = if text.starts_with('-') { 1 } else { 0 };
+    let end = match text[start..].find(|c: char| !c.is_ascii_alphanumeric()) {
+        Some(pos) => start + pos,
+        None => text.len()
+    };
+
+    let digits = &text[start..end];
+    let mut grouped = String::new();
+
+    for (i, ch) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(size) {
+            grouped.push(separator);
+        }
+
+        grouped.push(ch);
+    }
+
+    format!("{}{}{}", &text[..start], grouped, &text[end..])
+}
+
+impl Format {
+    /// Applies the whitespace separated `directives` in order, e.g. `hex sep` or `.3 sci`
+    pub fn apply(&mut self, directives: &str) -> Result<(), String> {
+        for directive in directives.split_whitespace() {
+            match directive {
+                "dec" => self.radix = Radix::Dec,
+                "hex" => self.radix = Radix::Hex,
+                "oct" => self.radix = Radix::Oct,
+                "bin" => self.radix = Radix::Bin,
+                "plain" => self.notation = Notation::Plain,
+                "sci" => self.notation = Notation::Scientific,
+                "eng" => self.notation = Notation::Engineering,
+                "sep" => self.separators = true,
+                "nosep" => self.separators = false,
+                "auto" => self.precision = None,
+                "reset" => *self = Format::default(),
+                _ => match directive.strip_prefix('.').map(|digits| digits.parse()) {
+                    Some(Ok(precision)) if precision <= decimal::MAX_SCALE as usize => self.precision = Some(precision),
+                    Some(_) => return Err(format!("Invalid precision: '{}', expected '.0' to '.{}'", directive, decimal::MAX_SCALE)),
+                    None => return Err(format!("Unknown format directive: '{}'", directive))
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn render(&self, value: &Value) -> String {
+        if let Value::Quantity(val) = value {
+            let (reading, unit) = val.parts();
+            return format!("{} {}", self.render(&reading), unit);
+        }
+
+        // Infinities and NaN have no exact value, they are shown as they are
+        let val = match value.to_rational() {
+            Some(val) => val,
+            None => return value.to_string()
+        };
+
+        if self.radix != Radix::Dec && val.is_integer() {
+            let integer = val.to_integer();
+            let digits = integer.abs().to_str_radix(self.radix.base());
+            let digits = if self.separators { group(&digits, 4, '_') } else { digits };
+
+            return format!("{}{}{}", if integer.is_negative() { "-" } else { "" }, self.radix.prefix(), digits);
+        }
+
+        let text = match (self.notation, self.precision) {
+            (Notation::Plain, None) => value.to_string(),
+            (Notation::Plain, Some(precision)) => Decimal::from_rational(&val, precision as u32, Rounding::HalfEven).to_string(),
+            (Notation::Scientific, precision) => scientific(&val, 1, precision, significant_digits(value, &val)),
+            (Notation::Engineering, precision) => scientific(&val, 3, precision, significant_digits(value, &val))
+        };
+
+        if self.separators && self.notation == Notation::Plain {
+            group(&text, 3, ',')
+        } else {
+            text
+        }
+    }
+}
+
+/// Lists the directives that give this format
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let radix = match self.radix {
+            Radix::Dec => "dec",
+            Radix::Hex => "hex",
+            Radix::Oct => "oct",
+            Radix::Bin => "bin"
+        };
+
+        write!(f, "{}", radix)?;
+
+        if let Some(precision) = self.precision {
+            write!(f, " .{}", precision)?;
+        }
+
+        match self.notation {
+            Notation::Plain => (),
+            Notation::Scientific => write!(f, " sci")?,
+            Notation::Engineering => write!(f, " eng")?
+        };
+
+        if self.separators {
+            write!(f, " sep")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Splits an `expr as directives` input at its last `as` word, the
+/// expression keeps its offsets in `input`
+pub fn split_suffix(input: &str) -> (&str, Option<&str>) {
+    let mut end = input.len();
+
+    while let Some(pos) = input[..end].rfind("as") {
+        let before = input[..pos].chars().next_back();
+        let after = input[pos + 2..].chars().next();
+
+        if before.is_some_and(char::is_whitespace) && after.is_none_or(char::is_whitespace) {
+            return (&input[..pos], Some(input[pos + 2..].trim()));
+        }
+
+        end = pos;
+    }
+
+    (input, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(value: Value, directives: &str) -> String {
+        let mut format = Format::default();
+
+        format.apply(directives).unwrap();
+        format.render(&value)
+    }
+
+    fn exact(numer: i64, denom: i64) -> Value {
+        Value::Exact(BigRational::new(BigInt::from(numer), BigInt::from(denom)))
+    }
+
+    #[test]
+    fn radixes() {
+        assert_eq!(render(Value::Int(255), "hex"), "0xff");
+        assert_eq!(render(Value::Float(-8.0), "oct"), "-0o10");
+        assert_eq!(render(Value::Int(0xdeadbeef), "hex sep"), "0xdead_beef");
+        assert_eq!(render(Value::Int(5), "bin sep"), "0b101");
+        assert_eq!(render(exact(10, 1), "bin"), "0b1010");
+        assert_eq!(render(Value::Float(2.5), "hex"), "2.5");
+    }
+
+    #[test]
+    fn precision_and_separators() {
+        assert_eq!(render(Value::Float(1.23456), ".3"), "1.235");
+        assert_eq!(render(Value::Float(0.25), ".1"), "0.2");
+        assert_eq!(render(exact(2, 3), ".4"), "0.6667");
+        assert_eq!(render(Value::Int(1234567), "sep"), "1,234,567");
+        assert_eq!(render(Value::Float(-1234567.891), ".2 sep"), "-1,234,567.89");
+        assert_eq!(render(exact(1234567, 2), "sep"), "1,234,567/2");
+    }
+
+    #[test]
+    fn scientific_notation() {
+        assert_eq!(render(Value::Float(12345.0), "sci"), "1.2345e4");
+        assert_eq!(render(Value::Float(0.00012), "sci"), "1.2e-4");
+        assert_eq!(render(Value::Float(9.999), ".2 sci"), "1.00e1");
+        assert_eq!(render(Value::Float(0.0), ".1 sci"), "0.0e0");
+        assert_eq!(render(exact(-1, 3), "sci"), "-3.333333333333333e-1");
+        assert_eq!(render(Value::Exact(BigRational::from_integer(Pow::pow(BigInt::from(10), 400u32))), "sci"), "1e400");
+    }
+
+    #[test]
+    fn engineering_notation() {
+        assert_eq!(render(Value::Float(12345.0), "eng"), "12.345e3");
+        assert_eq!(render(Value::Float(0.00012), "eng"), "120e-6");
+        assert_eq!(render(Value::Float(999.96), ".1 eng"), "1.0e3");
+        assert_eq!(render(Value::Int(1500), ".2 eng"), "1.50e3");
+    }
+
+    #[test]
+    fn non_finite_values_are_shown_as_they_are() {
+        assert_eq!(render(Value::Float(f64::INFINITY), "hex .2 sci"), "inf");
+        assert_eq!(render(Value::Float(f64::NAN), ".2 sep"), "NaN");
+    }
+
+    #[test]
+    fn directives() {
+        let mut format = Format::default();
+
+        assert!(format.apply("hex .3 eng sep").is_ok());
+        assert_eq!(format.to_string(), "hex .3 eng sep");
+        assert!(format.apply("reset oct").is_ok());
+        assert_eq!(format.to_string(), "oct");
+        assert_eq!(format.apply("hexa"), Err(String::from("Unknown format directive: 'hexa'")));
+        assert_eq!(format.apply(".x"), Err(String::from("Invalid precision: '.x', expected '.0' to '.40'")));
+    }
+
+    #[test]
+    fn suffix_splitting() {
+        assert_eq!(split_suffix("255 as hex\n"), ("255 ", Some("hex")));
+        assert_eq!(split_suffix("base as hex"), ("base ", Some("hex")));
+        assert_eq!(split_suffix("mass + 1"), ("mass + 1", None));
+        assert_eq!(split_suffix("1 as"), ("1 ", Some("")));
+    }
+}
@@ -1,55 +1,295 @@
+mod builtins;
+mod decimal;
+mod diagnostics;
+mod environment;
+mod error;
+mod format;
 mod parser;
+mod units;
+mod value;
 
+use std::collections::HashMap;
 use std::io::{self, Write};
-use std::panic;
+use std::thread;
 
 use colored::*;
 
-fn ast_visitor(root: &parser::ASTNode) -> f64 {
-    match root.node {
-        parser::Lexeme::Number(val) => val as f64,
-        parser::Lexeme::Plus => ast_visitor(match &(*root).left {
-            Some(expr) => expr,
-            None => panic!("Empty lhs on binary operator: {}", *root),
-        }) + ast_visitor(match &(*root).right {
-            Some(expr) => expr,
-            None => panic!("Empty rhs on binary operator: {}", *root),
-        }),
-        parser::Lexeme::Minus => ast_visitor(match &(*root).left {
-            Some(expr) => expr,
-            None => panic!("Empty lhs on binary operator: {}", *root),
-        }) - ast_visitor(match &(*root).right {
-            Some(expr) => expr,
-            None => panic!("Empty rhs on binary operator: {}", *root),
-        }),
-        parser::Lexeme::Mul => ast_visitor(match &(*root).left {
-            Some(expr) => expr,
-            None => panic!("Empty lhs on binary operator: {}", *root),
-        }) * ast_visitor(match &(*root).right {
-            Some(expr) => expr,
-            None => panic!("Empty rhs on binary operator: {}", *root),
-        }),
-        parser::Lexeme::Div => ast_visitor(match &(*root).left {
-            Some(expr) => expr,
-            None => panic!("Empty lhs on binary operator: {}", *root),
-        }) / ast_visitor(match &(*root).right {
-            Some(expr) => expr,
-            None => panic!("Empty rhs on binary operator: {}", *root),
-        }),
-        _ => panic!("Illegal code location!")
+use environment::{Environment, HistoryEntry, UserFunction};
+use error::{EvalError, LexError};
+use value::{BinaryOp, BitwiseOp, Mode, Value};
+
+const REPL_STACK_SIZE: usize = 256 * 1024 * 1024;
+
+fn operand<'a>(operand: &'a Option<Box<parser::ASTNode>>, root: &parser::ASTNode) -> Result<&'a parser::ASTNode, EvalError> {
+    match operand {
+        Some(expr) => Ok(expr),
+        None => Err(EvalError::MissingOperand(format!("{}", root)))
     }
 }
 
-fn main() {
+/// Evaluates `if(cond, then, else)` lazily so that recursive user functions
+/// can terminate
+fn if_visitor(root: &parser::ASTNode, env: &mut Environment) -> Result<Value, EvalError> {
+    if root.args.len() != 3 {
+        return Err(EvalError::ArityMismatch{name: String::from("if"), expected: String::from("3"), got: root.args.len()});
+    }
+
+    if !ast_visitor(&root.args[0], env)?.is_zero() {
+        ast_visitor(&root.args[1], env)
+    } else {
+        ast_visitor(&root.args[2], env)
+    }
+}
+
+fn call_visitor(name: &str, root: &parser::ASTNode, env: &mut Environment) -> Result<Value, EvalError> {
+    if name == "if" {
+        return if_visitor(root, env);
+    }
+
+    if let Some(builtin) = builtins::lookup(name) {
+        if !builtin.accepts(root.args.len()) {
+            return Err(EvalError::ArityMismatch{name: String::from(name), expected: builtin.arity(), got: root.args.len()});
+        }
+
+        let mut args = Vec::with_capacity(root.args.len());
+
+        for arg in &root.args {
+            match ast_visitor(arg, env)? {
+                Value::Quantity(_) => return Err(EvalError::DimensionedArgument{name: String::from(name), span: arg.span}),
+                val => args.push(val.to_f64())
+            }
+        }
+
+        // Built-in functions always compute in floating point
+        let result = (builtin.apply)(&args);
+
+        if result.is_nan() && !args.iter().any(|val| val.is_nan()) {
+            return Err(EvalError::Domain(String::from(name)));
+        }
+
+        if result.is_infinite() && args.iter().all(|val| val.is_finite()) {
+            if builtin.poles {
+                return Err(EvalError::Domain(String::from(name)));
+            }
+
+            return Err(EvalError::Overflow{span: root.span});
+        }
+
+        return Ok(Value::Float(result));
+    }
+
+    let function = match env.function(name) {
+        Some(val) => val,
+        None => return Err(EvalError::UnknownFunction(String::from(name)))
+    };
+
+    if function.params.len() != root.args.len() {
+        return Err(EvalError::ArityMismatch{name: String::from(name), expected: format!("{}", function.params.len()), got: root.args.len()});
+    }
+
+    let mut frame = HashMap::new();
+
+    for (param, arg) in function.params.iter().zip(&root.args) {
+        frame.insert(param.clone(), ast_visitor(arg, env)?);
+    }
+
+    env.enter(name, frame)?;
+    // Spans in the body point into the definition, not into the current input
+    let result = ast_visitor(&function.body, env).map_err(|e| e.relocate(root.span));
+    env.leave();
+
+    result
+}
+
+/// Stores a `def` statement in the environment, `source` is the statement text
+fn define(root: &parser::ASTNode, source: &str, env: &mut Environment) -> Result<String, EvalError> {
+    let signature = operand(&root.left, root)?;
+
+    let (name, params) = match &signature.node {
+        parser::Lexeme::Call(name) => (name.clone(), &signature.args),
+        _ => return Err(EvalError::UnexpectedNode(format!("{}", signature)))
+    };
+
+    let body = operand(&root.right, root)?.clone();
+
+    if name == "if" || builtins::lookup(&name).is_some() {
+        return Err(EvalError::BuiltinRedefinition(name));
+    }
+
+    let mut names : Vec<String> = Vec::new();
+
+    for param in params {
+        match &param.node {
+            parser::Lexeme::Identifier(param) if names.contains(param) => {
+                return Err(EvalError::DuplicateParameter{name, param: param.clone()});
+            },
+            parser::Lexeme::Identifier(param) => names.push(param.clone()),
+            _ => return Err(EvalError::UnexpectedNode(format!("{}", param)))
+        }
+    }
+
+    let signature = format!("{}({})", name, names.join(", "));
+
+    env.define(&name, UserFunction{params: names, body, source: String::from(source)});
+    Ok(signature)
+}
+
+/// Handles `:`-prefixed REPL commands
+fn command(input: &str, env: &mut Environment) -> Result<(), String> {
+    let words : Vec<&str> = input.split_whitespace().collect();
+
+    match words.as_slice() {
+        [":defs"] => {
+            let functions = env.functions();
+
+            if functions.is_empty() {
+                println!("[i] No user functions defined");
+            }
+
+            for function in functions {
+                println!("  {}", function.source);
+            }
+
+            Ok(())
+        },
+        [":depth"] => {
+            println!("[i] Maximum recursion depth: {}", env.max_depth());
+            Ok(())
+        },
+        [":depth", depth] => match depth.parse() {
+            Ok(depth) => env.set_max_depth(depth),
+            Err(_) => Err(format!("Invalid recursion depth: '{}'", depth))
+        },
+        [":mode"] => {
+            println!("[i] Number mode: {}", env.mode());
+            Ok(())
+        },
+        [":mode", args @ ..] => {
+            env.set_mode(Mode::parse(args)?);
+            Ok(())
+        },
+        [":fmt"] => {
+            println!("[i] Output format: {}", env.format());
+            Ok(())
+        },
+        [":fmt", directives @ ..] => {
+            let mut format = env.format();
+
+            format.apply(&directives.join(" "))?;
+            env.set_format(format);
+            Ok(())
+        },
+        _ => Err(format!("Unknown command: '{}'", input.trim()))
+    }
+}
+
+/// Lexes `source` with the literal rules of `mode`
+fn lex(source: &str, mode: Mode) -> Result<Vec<parser::Token>, LexError> {
+    match mode {
+        Mode::Integer => parser::Lexer::lex_integers(source),
+        _ => parser::Lexer::lex(source)
+    }
+}
+
+fn ast_visitor(root: &parser::ASTNode, env: &mut Environment) -> Result<Value, EvalError> {
+    match &root.node {
+        parser::Lexeme::Number(text) => Value::from_literal(text, env.mode(), root.span),
+        parser::Lexeme::Identifier(name) => match env.get(name) {
+            Some(val) => Ok(val),
+            None => Err(EvalError::UnknownIdentifier(name.clone()))
+        },
+        parser::Lexeme::OutRef(index) => env.history(*index),
+        parser::Lexeme::Call(name) => call_visitor(name, root, env),
+        parser::Lexeme::Assign => {
+            let target = operand(&root.left, root)?;
+
+            let name = match &target.node {
+                parser::Lexeme::Identifier(name) => name.clone(),
+                _ => return Err(EvalError::UnexpectedNode(format!("{}", target)))
+            };
+
+            let value = ast_visitor(operand(&root.right, root)?, env)?;
+
+            env.set(&name, value.clone());
+            Ok(value)
+        },
+        parser::Lexeme::UnaryPlus => ast_visitor(operand(&root.right, root)?, env),
+        parser::Lexeme::BitNot => ast_visitor(operand(&root.right, root)?, env)?.not(root.span),
+        parser::Lexeme::UnaryMinus => ast_visitor(operand(&root.right, root)?, env)?.neg(root.span),
+        parser::Lexeme::Unit(text) => {
+            let value = ast_visitor(operand(&root.right, root)?, env)?;
+            units::apply(value, units::resolve(text, root.span)?, root.span)
+        },
+        parser::Lexeme::Convert(text) => {
+            let value = ast_visitor(operand(&root.right, root)?, env)?;
+            units::convert(value, units::resolve(text, root.span)?, root.span)
+        },
+        parser::Lexeme::Plus | parser::Lexeme::Minus | parser::Lexeme::Mul | parser::Lexeme::Div | parser::Lexeme::Pow => {
+            let lhs = ast_visitor(operand(&root.left, root)?, env)?;
+            let rhs = ast_visitor(operand(&root.right, root)?, env)?;
+
+            let op = match root.node {
+                parser::Lexeme::Plus => BinaryOp::Add,
+                parser::Lexeme::Minus => BinaryOp::Sub,
+                parser::Lexeme::Mul => BinaryOp::Mul,
+                parser::Lexeme::Div => BinaryOp::Div,
+                _ => BinaryOp::Pow
+            };
+
+            Value::binary(op, lhs, rhs, root.span)
+        },
+        parser::Lexeme::BitAnd | parser::Lexeme::BitOr | parser::Lexeme::BitXor | parser::Lexeme::Shl | parser::Lexeme::Shr => {
+            let lhs = ast_visitor(operand(&root.left, root)?, env)?;
+            let rhs = ast_visitor(operand(&root.right, root)?, env)?;
+
+            let op = match root.node {
+                parser::Lexeme::BitAnd => BitwiseOp::And,
+                parser::Lexeme::BitOr => BitwiseOp::Or,
+                parser::Lexeme::BitXor => BitwiseOp::Xor,
+                parser::Lexeme::Shl => BitwiseOp::Shl,
+                _ => BitwiseOp::Shr
+            };
+
+            Value::bitwise(op, lhs, rhs, root.span)
+        },
+        _ => Err(EvalError::UnexpectedNode(format!("{}", root)))
+    }
+}
+
+fn repl() {
     let mut io_index : u32 = 1;
+    let mut env = Environment::new();
 
     println!("[i] Sazak's Basic Math Interpreter");
     println!("[i] Available operations:");
-    println!("      -> expr   : term ((PLUS | MINUS) term)*");
-    println!("      -> term   : factor ((MUL | DIV) factor)*");
-    println!("      -> factor : INTEGER | LPAREN expr RPAREN");
+    println!("      -> stmt   : DEF call ASSIGN conv | IDENT ASSIGN conv | conv");
+    println!("      -> conv   : expr (TO units)?");
+    println!("      -> expr   : bxor (BITOR bxor)*");
+    println!("      -> bxor   : band (BITXOR band)*");
+    println!("      -> band   : shift (BITAND shift)*");
+    println!("      -> shift  : sum ((SHL | SHR) sum)*");
+    println!("      -> sum    : term ((PLUS | MINUS) term)*");
+    println!("      -> term   : qty ((MUL | DIV) qty)*");
+    println!("      -> qty    : unary units?");
+    println!("      -> units  : UNIT (POW NUMBER)? ((MUL | DIV) UNIT (POW NUMBER)?)*");
+    println!("      -> unary  : (PLUS | MINUS | BITNOT) unary | power");
+    println!("      -> power  : factor (POW unary)?");
+    println!("      -> factor : NUMBER | IDENT | OUTREF | call | LPAREN conv RPAREN");
+    println!("      -> call   : IDENT LPAREN (expr (COMMA expr)*)? RPAREN");
     println!("\n[+] Put a '!' at the start of the expression to dump the lexemes");
-    println!("[+] Put a '!!' at the start of the expression to dump the AST");
+    println!("[+] Put a '!!' at the start of the expression to dump the AST, even a partial one");
+    println!("[+] Use '_' for the last output, '_<n>' or 'Out[<n>]' for the nth output");
+    println!("[+] Write integers as '0xff', '0o17' or '0b1010', with '_' separators like '1_000_000'");
+    println!("[+] Built-in functions: {}", builtins::BUILTINS.iter().map(|builtin| builtin.name).collect::<Vec<_>>().join(", "));
+    println!("[+] Units: {}", units::UNITS.iter().map(|unit| unit.name).collect::<Vec<_>>().join(", "));
+    println!("[+] Write quantities like '3 m' or '20 km/h', convert them with 'to', e.g. '100 degF to degC'");
+    println!("[+] Define functions with 'def name(a, b) = expr', use 'if(cond, then, else)' to stop recursion");
+    println!("[+] Enter ':defs' to list user functions, ':depth <n>' to set the maximum recursion depth");
+    println!("[+] Enter ':mode exact' for big integers and exact fractions, ':mode integer' for checked 128-bit integers");
+    println!("[+] Enter ':mode decimal [scale] [half-even|half-up|truncate]' for fixed-point decimals, e.g. ':mode decimal 2'");
+    println!("[+] Enter ':mode float' to switch back to floating point, 'float(x)' converts any number to it");
+    println!("[+] Enter ':fmt' with 'hex', 'oct', 'bin', 'dec', '.<digits>', 'sci', 'eng', 'plain', 'sep', 'nosep' or 'reset' to format outputs");
+    println!("[+] Add 'as <directives>' to format a single output, e.g. '255 as hex' or '1/3 as .3'");
     println!("[+] Enter 'exit' to exit the program");
 
     loop {
@@ -68,6 +308,14 @@ fn main() {
             break;
         }
 
+        if input_string.starts_with(':') {
+            if let Err(e) = command(input_string.as_str(), &mut env) {
+                println!("{}: {}", "CommandError".red(), e);
+            }
+
+            continue;
+        }
+
         if input_string.starts_with("!!") {
             dump_ast = true;
             input_string.remove(0);
@@ -77,12 +325,27 @@ fn main() {
             input_string.remove(0);
         }
 
-        let lexemes;
+        let (source, suffix) = format::split_suffix(input_string.as_str());
+        let mut output_format = env.format();
+
+        let applied = match suffix {
+            Some("") => Err(String::from("Expected format directives after 'as'")),
+            Some(directives) => output_format.apply(directives),
+            None => Ok(())
+        };
+
+        if let Err(e) = applied {
+            println!("{}: {}", "FormatError".red(), e);
+            env.record(HistoryEntry::Error);
+            io_index += 1;
+            continue;
+        }
 
-        match parser::Lexer::lex(input_string.as_str()) {
-            Ok(val) => lexemes = val,
+        let tokens = match lex(source, env.mode()) {
+            Ok(val) => val,
             Err(e) => {
-                println!("{}: {}", "LexerError".red(), e);
+                println!("{}", diagnostics::render("LexerError", &e, input_string.as_str()));
+                env.record(HistoryEntry::Error);
                 io_index += 1;
                 continue;
             }
@@ -91,33 +354,507 @@ fn main() {
         if dump_lexemes {
             println!("{}{}{}{}:", "Out".blue(), "[".blue(), io_index, "]".blue());
 
-            for i in 0..lexemes.len() {
-                println!("  {}: {}", i, lexemes[i]);
+            for (i, token) in tokens.iter().enumerate() {
+                println!("  {}: {}", i, token.lexeme);
             }
 
+            env.record(HistoryEntry::Dump);
             io_index += 1;
             continue;
         }
     
-        let mut _parser = parser::Parser::new(lexemes);
+        let mut _parser = parser::Parser::new(tokens).with_variables(env.variable_names());
+
+        if dump_ast {
+            let (ast, errors) = _parser.parse_partial();
+
+            for e in errors {
+                println!("{}", diagnostics::render("ParserError", &e, input_string.as_str()));
+            }
+
+            println!("{}{}{}{}: {}", "Out".blue(), "[".blue(), io_index, "]".blue(), ast);
+
+            env.record(HistoryEntry::Dump);
+            io_index += 1;
+            continue;
+        }
 
         let ast = match _parser.parse() {
             Ok(val) => val,
-            Err(e) => {
-                println!("{}: {}", "ParserError".red(), e);
+            Err(errors) => {
+                for e in errors {
+                    println!("{}", diagnostics::render("ParserError", &e, input_string.as_str()));
+                }
+
+                env.record(HistoryEntry::Error);
                 io_index += 1;
                 continue;
             }
         };
 
-        if dump_ast {
-            println!("{}{}{}{}: {}", "Out".blue(), "[".blue(), io_index, "]".blue(), ast);
+        if ast.node == parser::Lexeme::Def {
+            match define(&ast, source.trim(), &mut env) {
+                Ok(signature) => {
+                    println!("{}{}{}{}: {} defined", "Out".blue(), "[".blue(), io_index, "]".blue(), signature);
+                    env.record(HistoryEntry::Definition);
+                },
+                Err(e) => {
+                    println!("{}", diagnostics::render("EvalError", &e, input_string.as_str()));
+                    env.record(HistoryEntry::Error);
+                }
+            };
 
             io_index += 1;
             continue;
         }
-    
-        println!("{}{}{}{}: {}", "Out".blue(), "[".blue(), io_index, "]".blue(), ast_visitor(&ast));
+
+        match ast_visitor(&ast, &mut env) {
+            Ok(val) => {
+                println!("{}{}{}{}: {}", "Out".blue(), "[".blue(), io_index, "]".blue(), output_format.render(&val));
+                env.record(HistoryEntry::Value(val));
+            },
+            Err(e) => {
+                println!("{}", diagnostics::render("EvalError", &e, input_string.as_str()));
+                env.record(HistoryEntry::Error);
+            }
+        };
+
         io_index += 1;
     }
-}
\ No newline at end of file
+}
+
+fn main() {
+    // User function calls recurse through `ast_visitor`, so the REPL gets a
+    // stack that is large enough for `environment::MAX_DEPTH_LIMIT` calls
+    let handle = thread::Builder::new()
+        .stack_size(REPL_STACK_SIZE)
+        .spawn(repl)
+        .expect("Unable to start the REPL thread");
+
+    handle.join().expect("REPL thread panicked");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::Span;
+
+    fn eval_value(source: &str, env: &mut Environment) -> Result<Value, EvalError> {
+        let tokens = lex(source, env.mode()).expect("Lexing failed");
+        let ast = match parser::Parser::new(tokens).with_variables(env.variable_names()).parse() {
+            Ok(ast) => ast,
+            Err(errors) => panic!("Parsing failed: {}", errors[0])
+        };
+
+        if ast.node == parser::Lexeme::Def {
+            return define(&ast, source, env).map(|_| Value::Float(0.0));
+        }
+
+        ast_visitor(&ast, env)
+    }
+
+    fn eval_in(source: &str, env: &mut Environment) -> Result<f64, EvalError> {
+        eval_value(source, env).map(|val| val.to_f64())
+    }
+
+    /// Evaluates `source` in exact mode and renders the result
+    fn exact_in(source: &str, env: &mut Environment) -> Result<String, EvalError> {
+        env.set_mode(Mode::Exact);
+        eval_value(source, env).map(|val| val.to_string())
+    }
+
+    fn exact(source: &str) -> Result<String, EvalError> {
+        exact_in(source, &mut Environment::new())
+    }
+
+    fn eval(source: &str) -> Result<f64, EvalError> {
+        eval_in(source, &mut Environment::new())
+    }
+
+    #[test]
+    fn unary_minus() {
+        assert_eq!(eval("-3 + 4"), Ok(1.0));
+        assert_eq!(eval("2 * -(1+1)"), Ok(-4.0));
+        assert_eq!(eval("--5"), Ok(5.0));
+    }
+
+    #[test]
+    fn power() {
+        assert_eq!(eval("2 ^ 10"), Ok(1024.0));
+        assert_eq!(eval("2 ** 10"), Ok(1024.0));
+        assert_eq!(eval("2^3^2"), Ok(512.0));
+        assert_eq!(eval("-2^2"), Ok(-4.0));
+        assert_eq!(eval("(-2)^2"), Ok(4.0));
+        assert_eq!(eval("2^-1"), Ok(0.5));
+    }
+
+    #[test]
+    fn assignments() {
+        let mut env = Environment::new();
+
+        assert_eq!(eval_in("x = 3 * (4 + 1)", &mut env), Ok(15.0));
+        assert_eq!(eval_in("x + 1", &mut env), Ok(16.0));
+        assert_eq!(eval_in("x = x * 2", &mut env), Ok(30.0));
+        assert_eq!(eval_in("y", &mut env), Err(EvalError::UnknownIdentifier(String::from("y"))));
+        assert_eq!(eval_in("y + x", &mut env).unwrap_err().to_string(), "Unknown identifier: 'y'");
+    }
+
+    #[test]
+    fn builtin_functions() {
+        assert_eq!(eval("sqrt(16) + abs(-2)"), Ok(6.0));
+        assert_eq!(eval("max(1, 7, 3) - min(4, 2)"), Ok(5.0));
+        assert_eq!(eval("log(1000)"), Ok(3.0));
+        assert_eq!(eval("log(8, 2)"), Ok(3.0));
+        assert_eq!(eval("hypot(3, 4)"), Ok(5.0));
+    }
+
+    #[test]
+    fn builtin_function_errors() {
+        assert_eq!(eval("nope(1)"), Err(EvalError::UnknownFunction(String::from("nope"))));
+        assert_eq!(eval("sqrt(1, 2)").unwrap_err().to_string(), "Function 'sqrt' expects 1 argument(s), got 2");
+        assert_eq!(eval("max()").unwrap_err().to_string(), "Function 'max' expects at least 1 argument(s), got 0");
+    }
+
+    #[test]
+    fn user_functions() {
+        let mut env = Environment::new();
+
+        assert!(eval_in("def hyp(a, b) = sqrt(a^2 + b^2)", &mut env).is_ok());
+        assert_eq!(eval_in("hyp(3, 4)", &mut env), Ok(5.0));
+
+        assert!(eval_in("def fact(n) = if(n, n * fact(n - 1), 1)", &mut env).is_ok());
+        assert_eq!(eval_in("fact(5)", &mut env), Ok(120.0));
+    }
+
+    #[test]
+    fn user_function_parameters_shadow_globals() {
+        let mut env = Environment::new();
+
+        assert!(eval_in("a = 10", &mut env).is_ok());
+        assert!(eval_in("def f(a) = a + 1", &mut env).is_ok());
+        assert_eq!(eval_in("f(1) + a", &mut env), Ok(12.0));
+    }
+
+    #[test]
+    fn user_function_errors() {
+        let mut env = Environment::new();
+
+        assert!(eval_in("def f(a, b) = a + b", &mut env).is_ok());
+        assert_eq!(eval_in("f(1)", &mut env).unwrap_err().to_string(), "Function 'f' expects 2 argument(s), got 1");
+        assert_eq!(eval_in("def g(a, a) = a", &mut env).unwrap_err().to_string(), "Duplicate parameter 'a' in definition of 'g'");
+        assert_eq!(eval_in("def sqrt(a) = a", &mut env), Err(EvalError::BuiltinRedefinition(String::from("sqrt"))));
+    }
+
+    #[test]
+    fn recursion_depth_limit() {
+        let mut env = Environment::new();
+
+        assert!(env.set_max_depth(10).is_ok());
+        assert!(eval_in("def loop(n) = loop(n + 1)", &mut env).is_ok());
+        assert_eq!(eval_in("loop(0)", &mut env), Err(EvalError::RecursionLimit{name: String::from("loop"), depth: 10}));
+        assert_eq!(eval_in("loop", &mut env), Err(EvalError::UnknownIdentifier(String::from("loop"))));
+    }
+
+    #[test]
+    fn evaluation_errors() {
+        assert_eq!(eval("1 / (2 - 2)"), Err(EvalError::DivisionByZero{span: Span::new(0, 11)}));
+        assert_eq!(eval("1e308 * 10"), Err(EvalError::Overflow{span: Span::new(0, 10)}));
+        assert_eq!(eval("10 ^ 400"), Err(EvalError::Overflow{span: Span::new(0, 8)}));
+        assert_eq!(eval("sqrt(-1)"), Err(EvalError::Domain(String::from("sqrt"))));
+        assert_eq!(eval("ln(0)"), Err(EvalError::Domain(String::from("ln"))));
+        assert_eq!(eval("log(0)"), Err(EvalError::Domain(String::from("log"))));
+        assert_eq!(eval("log2(0)"), Err(EvalError::Domain(String::from("log2"))));
+        assert_eq!(eval("log(8, 1)"), Err(EvalError::Domain(String::from("log"))));
+        assert_eq!(eval("log(0, 100)"), Err(EvalError::Domain(String::from("log"))));
+        assert_eq!(eval("(-8)^(1/3)"), Err(EvalError::Domain(String::from("^"))));
+        assert_eq!(exact("(-8)^(1/3)"), Err(EvalError::Domain(String::from("^"))));
+        assert_eq!(eval("0^-1"), Err(EvalError::DivisionByZero{span: Span::new(0, 4)}));
+        assert_eq!(integer("0^-1"), Err(EvalError::DivisionByZero{span: Span::new(0, 4)}));
+        assert_eq!(eval("exp(1000)"), Err(EvalError::Overflow{span: Span::new(0, 9)}));
+        assert_eq!(eval("x + 1"), Err(EvalError::UnknownIdentifier(String::from("x"))));
+    }
+
+    /// Evaluates `source` and records its outcome like the REPL does
+    fn record(source: &str, env: &mut Environment) -> Result<Value, EvalError> {
+        let result = eval_value(source, env);
+
+        env.record(match &result {
+            Ok(val) => HistoryEntry::Value(val.clone()),
+            Err(_) => HistoryEntry::Error
+        });
+
+        result
+    }
+
+    #[test]
+    fn history_references() {
+        let mut env = Environment::new();
+
+        assert_eq!(eval_in("_", &mut env), Err(EvalError::MissingHistory(0)));
+        assert!(record("2 * 3", &mut env).is_ok());
+        assert_eq!(eval_in("_ + 1", &mut env), Ok(7.0));
+        assert!(record("10", &mut env).is_ok());
+        assert!(record("100", &mut env).is_ok());
+        assert_eq!(eval_in("_", &mut env), Ok(100.0));
+        assert_eq!(eval_in("_2", &mut env), Ok(10.0));
+        assert_eq!(eval_in("Out[2] + _1", &mut env), Ok(16.0));
+        assert_eq!(eval_in("_4", &mut env), Err(EvalError::MissingHistory(4)));
+        assert_eq!(eval_in("Out[0]", &mut env), Err(EvalError::MissingHistory(0)));
+    }
+
+    #[test]
+    fn history_entries_without_a_value() {
+        let mut env = Environment::new();
+
+        env.record(HistoryEntry::Dump);
+        assert!(record("1 / 0", &mut env).is_err());
+        assert!(eval_in("def f(a) = a", &mut env).is_ok());
+        env.record(HistoryEntry::Definition);
+
+        assert_eq!(eval_in("_1", &mut env), Err(EvalError::NoValueInHistory{index: 1, reason: "a dump"}));
+        assert_eq!(eval_in("Out[2]", &mut env), Err(EvalError::NoValueInHistory{index: 2, reason: "an error"}));
+        assert_eq!(eval_in("_", &mut env).unwrap_err().to_string(), "Out[3] was a definition and has no value");
+    }
+
+    #[test]
+    fn malformed_history_references() {
+        assert!(matches!(lex("Out[x]", Mode::Float), Err(LexError::MalformedHistoryReference{..})));
+        assert!(matches!(lex("Out[3", Mode::Float), Err(LexError::MalformedHistoryReference{..})));
+        assert_eq!(lex("_99999999999", Mode::Float).err(), Some(LexError::InvalidHistoryReference{text: String::from("_99999999999"), span: Span::new(0, 12)}));
+    }
+
+    #[test]
+    fn malformed_ast_is_an_error() {
+        let mut env = Environment::new();
+        let node = parser::ASTNode{node: parser::Lexeme::Plus, left: None, right: None, args: Vec::new(), span: Span::new(0, 1)};
+
+        assert!(matches!(ast_visitor(&node, &mut env), Err(EvalError::MissingOperand(_))));
+        assert!(matches!(ast_visitor(&parser::ASTNode::leaf(parser::Lexeme::Comma, Span::new(0, 1)), &mut env), Err(EvalError::UnexpectedNode(_))));
+    }
+
+    #[test]
+    fn float_literals() {
+        assert_eq!(eval("1.25 * 2"), Ok(2.5));
+        assert_eq!(eval(".5 + 1e-1"), Ok(0.6));
+        assert_eq!(eval("6.02E23 / 2"), Ok(3.01e23));
+    }
+
+    #[test]
+    fn unary_plus() {
+        assert_eq!(eval("+7 - +2"), Ok(5.0));
+    }
+
+    #[test]
+    fn exact_arithmetic() {
+        assert_eq!(exact("1/3 + 1/6"), Ok(String::from("1/2")));
+        assert_eq!(exact("1/3 * 3"), Ok(String::from("1")));
+        assert_eq!(exact("0.1 + 0.2"), Ok(String::from("3/10")));
+        assert_eq!(exact("2.5e-3"), Ok(String::from("1/400")));
+        assert_eq!(exact("2^100"), Ok(String::from("1267650600228229401496703205376")));
+        assert_eq!(exact("(2/3)^-2"), Ok(String::from("9/4")));
+        assert_eq!(exact("-(1/2)"), Ok(String::from("-1/2")));
+    }
+
+    #[test]
+    fn exact_user_functions() {
+        let mut env = Environment::new();
+
+        assert!(exact_in("def fact(n) = if(n, n * fact(n - 1), 1)", &mut env).is_ok());
+        assert_eq!(exact_in("fact(25)", &mut env), Ok(String::from("15511210043330985984000000")));
+    }
+
+    #[test]
+    fn exact_mixed_with_float() {
+        assert_eq!(exact("sqrt(16) / 8"), Ok(String::from("0.5")));
+        assert_eq!(exact("float(1/4)"), Ok(String::from("0.25")));
+        assert_eq!(exact("4^(1/2)"), Ok(String::from("2")));
+        assert_eq!(exact("1/2 + 0.25 * float(1)"), Ok(String::from("0.75")));
+    }
+
+    #[test]
+    fn exact_errors() {
+        assert_eq!(exact("1 / (1/2 - 2/4)"), Err(EvalError::DivisionByZero{span: Span::new(0, 15)}));
+        assert_eq!(exact("0^-1"), Err(EvalError::DivisionByZero{span: Span::new(0, 4)}));
+        assert_eq!(exact("10^10^9"), Err(EvalError::Overflow{span: Span::new(0, 7)}));
+        assert_eq!(exact("1e99999999"), Err(EvalError::Overflow{span: Span::new(0, 10)}));
+        assert_eq!(exact("(-1)^10^12"), Ok(String::from("1")));
+    }
+
+    #[test]
+    fn exact_products_are_bounded() {
+        let mut env = Environment::new();
+
+        assert!(exact_in("def sq(n, k) = if(k, sq(n*n, k - 1), n)", &mut env).is_ok());
+        assert_eq!(exact_in("sq(3, 4)", &mut env), Ok(String::from("43046721")));
+        assert!(matches!(exact_in("sq(1 << 2^22, 40)", &mut env), Err(EvalError::Overflow{..})));
+        assert_eq!(exact("(1 << 2^23) * (1 << 2^23)"), Err(EvalError::Overflow{span: Span::new(0, 25)}));
+        assert_eq!(exact("1/(1 << 2^23) + 1/((1 << 2^23) + 1)"), Err(EvalError::Overflow{span: Span::new(0, 35)}));
+    }
+
+    fn integer_in(source: &str, env: &mut Environment) -> Result<Value, EvalError> {
+        env.set_mode(Mode::Integer);
+        eval_value(source, env)
+    }
+
+    fn integer(source: &str) -> Result<Value, EvalError> {
+        integer_in(source, &mut Environment::new())
+    }
+
+    #[test]
+    fn integer_arithmetic() {
+        assert_eq!(integer("7 / 2 + -7 / 2"), Ok(Value::Int(0)));
+        assert_eq!(integer("2^126 - 1 + 2^126"), Ok(Value::Int(i128::MAX)));
+        assert_eq!(integer("2^-1"), Ok(Value::Int(0)));
+        assert_eq!(integer("(-1)^-3"), Ok(Value::Int(-1)));
+        assert_eq!(integer("sqrt(16)"), Ok(Value::Float(4.0)));
+    }
+
+    #[test]
+    fn integer_overflow_points_at_the_subexpression() {
+        assert_eq!(integer("1 + 2^127"), Err(EvalError::Overflow{span: Span::new(4, 9)}));
+        assert_eq!(integer("(2^126 + 2^126) * 0"), Err(EvalError::Overflow{span: Span::new(0, 15)}));
+        assert_eq!(integer("-(-2^126 * 2) - 1"), Err(EvalError::Overflow{span: Span::new(0, 13)}));
+        assert_eq!(integer("3 * (1 / 0)"), Err(EvalError::DivisionByZero{span: Span::new(4, 11)}));
+        assert_eq!(integer("0 ^ -1"), Err(EvalError::DivisionByZero{span: Span::new(0, 6)}));
+    }
+
+    #[test]
+    fn integer_errors_in_user_functions_point_at_the_call() {
+        let mut env = Environment::new();
+
+        assert!(integer_in("def inv(n) = 1 / n", &mut env).is_ok());
+        assert_eq!(integer_in("1 + inv(0)", &mut env), Err(EvalError::DivisionByZero{span: Span::new(4, 10)}));
+    }
+
+    #[test]
+    fn integer_literals_are_checked_while_lexing() {
+        let max = "170141183460469231731687303715884105727";
+        let tokens = lex(max, Mode::Integer).unwrap();
+
+        assert_eq!(tokens[0].lexeme, parser::Lexeme::Number(String::from(max)));
+        assert_eq!(lex("1 + 170141183460469231731687303715884105728", Mode::Integer).unwrap_err(), LexError::IntegerOverflow{
+            text: String::from("170141183460469231731687303715884105728"),
+            span: Span::new(4, 43)
+        });
+        assert_eq!(lex("1.5", Mode::Integer).unwrap_err(), LexError::NotAnInteger{text: String::from("1.5"), span: Span::new(0, 3)});
+        assert!(lex("1.5", Mode::Float).is_ok());
+    }
+
+    fn decimal(source: &str, mode: &str) -> Result<String, EvalError> {
+        let mut env = Environment::new();
+        let args : Vec<&str> = mode.split_whitespace().collect();
+
+        env.set_mode(Mode::parse(&args).unwrap());
+        eval_value(source, &mut env).map(|val| val.to_string())
+    }
+
+    #[test]
+    fn decimal_arithmetic() {
+        assert_eq!(decimal("0.1 + 0.2", "decimal"), Ok(String::from("0.30")));
+        assert_eq!(decimal("19.99 * 3", "decimal"), Ok(String::from("59.97")));
+        assert_eq!(decimal("1 / 3", "decimal 4"), Ok(String::from("0.3333")));
+        assert_eq!(decimal("100 / 3 * 3", "decimal"), Ok(String::from("99.99")));
+        assert_eq!(decimal("1.05 ^ 2", "decimal 4"), Ok(String::from("1.1025")));
+        assert_eq!(decimal("-(1/8)", "decimal 2"), Ok(String::from("-0.12")));
+    }
+
+    #[test]
+    fn decimal_rounding_modes() {
+        assert_eq!(decimal("2.5 * 1", "decimal 0"), Ok(String::from("2")));
+        assert_eq!(decimal("2.5 * 1", "decimal 0 half-up"), Ok(String::from("3")));
+        assert_eq!(decimal("-2.5 * 1", "decimal half-up 0"), Ok(String::from("-3")));
+        assert_eq!(decimal("2.99 * 1", "decimal 0 truncate"), Ok(String::from("2")));
+        assert_eq!(decimal("0.125", "decimal half-up"), Ok(String::from("0.13")));
+    }
+
+    #[test]
+    fn decimal_mixed_and_errors() {
+        assert_eq!(decimal("sqrt(2.25) + 0", "decimal"), Ok(String::from("1.5")));
+        assert_eq!(decimal("1 / (0.1 - 0.1)", "decimal"), Err(EvalError::DivisionByZero{span: Span::new(0, 15)}));
+        assert_eq!(Mode::parse(&["decimal", "99"]).unwrap_err(), "Invalid decimal option: '99', expected a scale up to 40 or a rounding mode");
+        assert!(Mode::parse(&["decimal", "2", "half-up", "3"]).is_err());
+    }
+
+    #[test]
+    fn bitwise_operators() {
+        assert_eq!(eval("0xF0 | 0x0F"), Ok(255.0));
+        assert_eq!(eval("0b1100 & 0b1010"), Ok(8.0));
+        assert_eq!(eval("6 xor 3"), Ok(5.0));
+        assert_eq!(eval("~0"), Ok(-1.0));
+        assert_eq!(eval("1 << 4 + 1"), Ok(32.0));
+        assert_eq!(eval("-16 >> 2"), Ok(-4.0));
+        assert_eq!(eval("1_000 >> 100"), Ok(0.0));
+        assert_eq!(eval("4.0 & 5"), Ok(4.0));
+    }
+
+    #[test]
+    fn bitwise_operators_keep_the_number_type() {
+        assert_eq!(integer("0x7f << 8 | 0xff"), Ok(Value::Int(0x7fff)));
+        assert_eq!(integer("1 << 127"), Err(EvalError::Overflow{span: Span::new(0, 8)}));
+        assert_eq!(exact("1 << 127"), Ok(String::from("170141183460469231731687303715884105728")));
+        assert_eq!(exact("(4/2) | 1"), Ok(String::from("3")));
+        assert_eq!(decimal("0xff & 0x0f", "decimal"), Ok(String::from("15.00")));
+    }
+
+    #[test]
+    fn bitwise_operator_errors() {
+        assert_eq!(eval("1.5 & 1"), Err(EvalError::NotAnInteger{span: Span::new(0, 7)}));
+        assert_eq!(eval("2 * ~0.5"), Err(EvalError::NotAnInteger{span: Span::new(4, 8)}));
+        assert_eq!(exact("1/2 | 1"), Err(EvalError::NotAnInteger{span: Span::new(0, 7)}));
+        assert_eq!(eval("1 << -1"), Err(EvalError::NegativeShift{span: Span::new(0, 7)}));
+    }
+
+    /// Evaluates `source` in float mode and renders the result
+    fn show(source: &str) -> Result<String, EvalError> {
+        eval_value(source, &mut Environment::new()).map(|val| val.to_string())
+    }
+
+    #[test]
+    fn quantities_check_dimensions() {
+        assert_eq!(show("3 m + 2 m"), Ok(String::from("5 m")));
+        assert_eq!(show("1 km + 500 m"), Ok(String::from("1.5 km")));
+        assert_eq!(show("10 N / 2 kg"), Ok(String::from("5 m/s^2")));
+        assert_eq!(eval("6 m / 2 m"), Ok(3.0));
+        assert_eq!(eval("3 m + 2 s"), Err(EvalError::DimensionMismatch{lhs: String::from("m"), rhs: String::from("s"), span: Span::new(0, 9)}));
+        assert_eq!(eval("1 + 2 m"), Err(EvalError::DimensionMismatch{lhs: String::from("1"), rhs: String::from("m"), span: Span::new(0, 7)}));
+        assert_eq!(eval("sqrt(4 m^2)"), Err(EvalError::DimensionedArgument{name: String::from("sqrt"), span: Span::new(5, 10)}));
+        assert_eq!(eval("(2 m)^0.5"), Err(EvalError::FractionalPower{span: Span::new(0, 9)}));
+    }
+
+    #[test]
+    fn variables_shadow_unit_names() {
+        let mut env = Environment::new();
+
+        assert!(eval_in("g = 2", &mut env).is_ok());
+        assert_eq!(eval_value("5 kg * g", &mut env).map(|val| val.to_string()), Ok(String::from("10 kg")));
+        assert_eq!(eval_in("3 m^-1 * 2 m", &mut env), Ok(6.0));
+    }
+
+    #[test]
+    fn unit_conversions() {
+        assert_eq!(exact("20 km/h to m/s"), Ok(String::from("50/9 m/s")));
+        assert_eq!(exact("1 kWh to J"), Ok(String::from("3600000 J")));
+        assert_eq!(exact("(3 m)^2 to cm^2"), Ok(String::from("90000 cm^2")));
+        assert_eq!(integer("3 km to m").map(|val| val.to_string()), Ok(String::from("3000 m")));
+        assert_eq!(decimal("1 mi to km", "decimal 2"), Ok(String::from("1.61 km")));
+        assert_eq!(eval("1 m to s"), Err(EvalError::DimensionMismatch{lhs: String::from("m"), rhs: String::from("s"), span: Span::new(0, 8)}));
+    }
+
+    #[test]
+    fn temperature_offsets() {
+        assert_eq!(exact("100 degF to degC"), Ok(String::from("340/9 degC")));
+        assert_eq!(exact("300 K to degC"), Ok(String::from("537/20 degC")));
+        assert_eq!(show("-40 degC to degF"), Ok(String::from("-40 degF")));
+        assert_eq!(exact("20 degC - 5 degC"), Ok(String::from("15 K")));
+        assert_eq!(exact("68 degF - 32 degF"), Ok(String::from("20 K")));
+        assert_eq!(exact("20 degC + 9 deltaF"), Ok(String::from("25 degC")));
+        assert_eq!(eval("20 degC + 5 degC"), Err(EvalError::OffsetArithmetic{span: Span::new(0, 16)}));
+        assert_eq!(eval("2 * 20 degC"), Err(EvalError::OffsetArithmetic{span: Span::new(0, 11)}));
+        assert_eq!(eval("1 degC/s"), Err(EvalError::InvalidUnit{unit: String::from("degC/s"), span: Span::new(0, 8)}));
+        assert_eq!(eval("-300 degC to K"), Err(EvalError::BelowAbsoluteZero{span: Span::new(0, 9)}));
+        assert_eq!(eval("-500 degF"), Err(EvalError::BelowAbsoluteZero{span: Span::new(0, 9)}));
+        assert_eq!(eval("-1 K to degC"), Err(EvalError::BelowAbsoluteZero{span: Span::new(0, 12)}));
+        assert_eq!(eval("-200 degC - 100 deltaC"), Err(EvalError::BelowAbsoluteZero{span: Span::new(0, 22)}));
+        assert_eq!(exact("0 K to degC"), Ok(String::from("-5463/20 degC")));
+    }
+}
@@ -1,16 +1,62 @@
+use std::collections::HashSet;
 use std::fmt;
+use std::iter::Peekable;
+use std::str::CharIndices;
 use std::vec;
 use std::boxed::Box;
 
-#[derive(Clone, Copy, Eq, PartialEq)]
+use crate::diagnostics::Diagnostic;
+use crate::error::{LexError, ParseError};
+use crate::units;
+
+/// Byte range `start..end` of a token or an error in the source line
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span{start, end}
+    }
+
+    /// Smallest span covering both `self` and `other`
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Lexeme {
-    Number(i128),
+    Number(String),
+    Identifier(String),
+    OutRef(Option<u32>),
+    Call(String),
+    Def,
+    Assign,
+    Comma,
     Plus,
     Minus,
     Mul,
     Div,
+    Pow,
+    BitAnd,
+    BitOr,
+    BitXor,
+    BitNot,
+    Shl,
+    Shr,
+    To,
+    UnaryPlus,
+    UnaryMinus,
+    /// AST only, the operand given in a unit, e.g. `Unit(km/h)`
+    Unit(String),
+    /// AST only, the operand converted to a unit with `to`
+    Convert(String),
     Lparen(u32),
     Rparen(u32),
+    Error,
     Nil
 }
 
@@ -18,107 +64,362 @@ impl fmt::Display for Lexeme {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Lexeme::Number(val) => write!(f, "Number({})", val),
+            Lexeme::Identifier(name) => write!(f, "Identifier({})", name),
+            Lexeme::OutRef(Some(index)) => write!(f, "OutRef({})", index),
+            Lexeme::OutRef(None) => write!(f, "OutRef(_)"),
+            Lexeme::Call(name) => write!(f, "Call({})", name),
+            Lexeme::Def => write!(f, "Def"),
+            Lexeme::Assign => write!(f, "Assign(=)"),
+            Lexeme::Comma => write!(f, "Comma(,)"),
             Lexeme::Plus => write!(f, "Plus(+)"),
             Lexeme::Minus => write!(f, "Minus(-)"),
             Lexeme::Mul => write!(f, "Mul(*)"),
             Lexeme::Div => write!(f, "Div(/)"),
+            Lexeme::Pow => write!(f, "Pow(^)"),
+            Lexeme::BitAnd => write!(f, "BitAnd(&)"),
+            Lexeme::BitOr => write!(f, "BitOr(|)"),
+            Lexeme::BitXor => write!(f, "BitXor(xor)"),
+            Lexeme::BitNot => write!(f, "BitNot(~)"),
+            Lexeme::Shl => write!(f, "Shl(<<)"),
+            Lexeme::Shr => write!(f, "Shr(>>)"),
+            Lexeme::To => write!(f, "To(to)"),
+            Lexeme::UnaryPlus => write!(f, "UnaryPlus(+)"),
+            Lexeme::UnaryMinus => write!(f, "UnaryMinus(-)"),
+            Lexeme::Unit(text) => write!(f, "Unit({})", text),
+            Lexeme::Convert(text) => write!(f, "Convert({})", text),
             Lexeme::Lparen(level) => write!(f, "Lparen({})", level),
             Lexeme::Rparen(level) => write!(f, "Rparen({})", level),
+            Lexeme::Error => write!(f, "<Error>"),
             Lexeme::Nil => write!(f, "<Nil>",)
         }
     }
 }
 
+/// A lexeme together with the place it was read from
+#[derive(Clone, Debug)]
+pub struct Token {
+    pub lexeme: Lexeme,
+    pub span: Span
+}
+
+/// Number literals keep their source text without the `_` digit separators,
+/// e.g. `3.14`, `.5`, `6.02E23` or `0xff`
 pub struct Lexer {}
 
+/// Splits a number literal into its radix and digits, e.g. `0x1f` into `(16, "1f")`
+pub fn split_radix(text: &str) -> (u32, &str) {
+    match text.get(..2) {
+        Some("0x") => (16, &text[2..]),
+        Some("0o") => (8, &text[2..]),
+        Some("0b") => (2, &text[2..]),
+        _ => (10, text)
+    }
+}
+
+/// Byte offset of the next unread character
+fn offset(chars: &mut Peekable<CharIndices>, source: &str) -> usize {
+    match chars.peek() {
+        Some(&(index, _)) => index,
+        None => source.len()
+    }
+}
+
 impl Lexer {
-    pub fn lex(source: &str) -> Result<Vec<Lexeme>, String> {
-        let mut lexemes = vec::Vec::new();
+    pub fn lex(source: &str) -> Result<Vec<Token>, LexError> {
+        let mut tokens = vec::Vec::new();
         let mut paren_depth = 0;
+        let mut chars = source.char_indices().peekable();
 
-        for (char_index, ch) in source.chars().enumerate() {
+        while let Some((char_index, ch)) = chars.next() {
             if ch.is_whitespace() {
                 continue;
             }
 
-            if ch.is_digit(10) {
-                if lexemes.len() > 0 {
-                    let last_elem : Lexeme = match lexemes.pop() {
-                        Some(val) => val,
-                        _ => {
-                            return Err(String::from("Could not get the last value from lexeme vector."));
+            let lexeme = if ch == '0' && matches!(chars.peek(), Some(&(_, 'x' | 'X' | 'o' | 'O' | 'b' | 'B'))) {
+                let prefix = chars.next().map(|(_, prefix)| prefix.to_ascii_lowercase()).unwrap_or('x');
+                let radix = match prefix {
+                    'x' => 16,
+                    'o' => 8,
+                    _ => 2
+                };
+
+                let mut text = format!("0{}", prefix);
+
+                while let Some(&(_, next)) = chars.peek() {
+                    if next.is_digit(radix) {
+                        text.push(next);
+                    } else if next == '_' && text.len() > 2 {
+                        chars.next();
+
+                        match chars.peek() {
+                            Some(&(_, digit)) if digit.is_digit(radix) => continue,
+                            _ => {
+                                text.push(next);
+                                return Err(LexError::MalformedNumber{text, span: Span::new(char_index, offset(&mut chars, source))});
+                            }
                         }
-                    };
+                    } else if next.is_alphanumeric() || next == '_' || next == '.' {
+                        text.push(next);
+                        chars.next();
+                        return Err(LexError::MalformedNumber{text, span: Span::new(char_index, offset(&mut chars, source))});
+                    } else {
+                        break;
+                    }
 
-                    match last_elem {
-                        Lexeme::Number(val) => lexemes.push(Lexeme::Number(val * 10 + match ch.to_digit(10) {
-                            Some(val) => val as i128,
+                    chars.next();
+                }
+
+                if text.len() == 2 {
+                    return Err(LexError::MalformedNumber{text, span: Span::new(char_index, offset(&mut chars, source))});
+                }
+
+                Lexeme::Number(text)
+            } else if ch.is_ascii_digit() || ch == '.' {
+                let mut text = String::from(ch);
+                let mut seen_point = ch == '.';
+                let mut seen_exponent = false;
+
+                while let Some(&(next_index, next)) = chars.peek() {
+                    if next.is_ascii_digit() {
+                        text.push(next);
+                    } else if next == '_' {
+                        // A separator has to sit between two digits, e.g. `1_000`
+                        let after_digit = text.ends_with(|c: char| c.is_ascii_digit());
+
+                        chars.next();
+
+                        match chars.peek() {
+                            Some(&(_, digit)) if digit.is_ascii_digit() && after_digit => continue,
                             _ => {
-                                return Err(format!("Inconvertible digit(?): '{0}' at position {1}.", ch, char_index));
-                            }}
-                        )),
-                        _ => {
-                            lexemes.push(last_elem);
-                            lexemes.push(Lexeme::Number(match ch.to_digit(10) {
-                                Some(val) => val as i128,
-                                _ => {
-                                    return Err(format!("Inconvertible digit(?): '{0}' at position {1}.", ch, char_index));
-                                }
-                            }));
+                                text.push(next);
+                                return Err(LexError::MalformedNumber{text, span: Span::new(char_index, offset(&mut chars, source))});
+                            }
+                        }
+                    } else if next == '.' && !seen_point && !seen_exponent {
+                        seen_point = true;
+                        text.push(next);
+                    } else if next == '.' {
+                        text.push(next);
+                        return Err(LexError::MalformedNumber{text, span: Span::new(char_index, next_index + 1)});
+                    } else if (next == 'e' || next == 'E') && !seen_exponent {
+                        seen_exponent = true;
+                        text.push(next);
+                        chars.next();
+
+                        if let Some(&(_, sign)) = chars.peek() {
+                            if sign == '+' || sign == '-' {
+                                text.push(sign);
+                                chars.next();
+                            }
+                        }
+
+                        match chars.peek() {
+                            Some(&(_, digit)) if digit.is_ascii_digit() => (),
+                            _ => return Err(LexError::MissingExponent{text, span: Span::new(char_index, offset(&mut chars, source))})
                         }
+
+                        continue;
+                    } else {
+                        break;
                     }
-                } else {
-                    lexemes.push(Lexeme::Number(match ch.to_digit(10) {
-                        Some(val) => val as i128,
-                        _ => {
-                            return Err(format!("Inconvertible digit(?): '{0}' at position {1}.", ch, char_index));
+
+                    chars.next();
+                }
+
+                if text.parse::<f64>().is_err() {
+                    return Err(LexError::MalformedNumber{text, span: Span::new(char_index, offset(&mut chars, source))});
+                }
+
+                Lexeme::Number(text)
+            } else if ch.is_alphabetic() || ch == '_' {
+                let mut name = String::from(ch);
+
+                while let Some(&(_, next)) = chars.peek() {
+                    if !(next.is_alphanumeric() || next == '_') {
+                        break;
+                    }
+
+                    name.push(next);
+                    chars.next();
+                }
+
+                if name == "_" {
+                    Lexeme::OutRef(None)
+                } else if name.len() > 1 && name.starts_with('_') && name[1..].chars().all(|c| c.is_ascii_digit()) {
+                    match name[1..].parse() {
+                        Ok(index) => Lexeme::OutRef(Some(index)),
+                        Err(_) => return Err(LexError::InvalidHistoryReference{
+                            span: Span::new(char_index, offset(&mut chars, source)),
+                            text: name
+                        })
+                    }
+                } else if name == "Out" && chars.peek().map(|&(_, next)| next) == Some('[') {
+                    chars.next();
+
+                    let mut digits = String::new();
+
+                    while let Some(&(_, next)) = chars.peek() {
+                        if !next.is_ascii_digit() {
+                            break;
                         }
-                    }));
+
+                        digits.push(next);
+                        chars.next();
+                    }
+
+                    match (digits.parse(), chars.next()) {
+                        (Ok(index), Some((_, ']'))) => Lexeme::OutRef(Some(index)),
+                        _ => return Err(LexError::MalformedHistoryReference{span: Span::new(char_index, offset(&mut chars, source))})
+                    }
+                } else if name == "def" {
+                    Lexeme::Def
+                } else if name == "xor" {
+                    Lexeme::BitXor
+                } else if name == "to" {
+                    Lexeme::To
+                } else {
+                    Lexeme::Identifier(name)
                 }
             } else if ch == '(' {
-                lexemes.push(Lexeme::Lparen(paren_depth));
                 paren_depth += 1;
+                Lexeme::Lparen(paren_depth - 1)
             } else if ch == ')' {
+                if paren_depth == 0 {
+                    return Err(LexError::UnbalancedParen{span: Span::new(char_index, char_index + 1)});
+                }
+
                 paren_depth -= 1;
-                lexemes.push(Lexeme::Rparen(paren_depth));
+                Lexeme::Rparen(paren_depth)
             } else {
                 match ch {
-                    '+' => lexemes.push(Lexeme::Plus),
-                    '-' => lexemes.push(Lexeme::Minus),
-                    '*' => lexemes.push(Lexeme::Mul),
-                    '/' => lexemes.push(Lexeme::Div),
+                    '+' => Lexeme::Plus,
+                    '-' => Lexeme::Minus,
+                    '*' => {
+                        if chars.peek().map(|&(_, next)| next) == Some('*') {
+                            chars.next();
+                            Lexeme::Pow
+                        } else {
+                            Lexeme::Mul
+                        }
+                    },
+                    '^' => Lexeme::Pow,
+                    '/' => Lexeme::Div,
+                    '&' => Lexeme::BitAnd,
+                    '|' => Lexeme::BitOr,
+                    '~' => Lexeme::BitNot,
+                    '<' | '>' if chars.peek().map(|&(_, next)| next) == Some(ch) => {
+                        chars.next();
+
+                        if ch == '<' {
+                            Lexeme::Shl
+                        } else {
+                            Lexeme::Shr
+                        }
+                    },
+                    '=' => Lexeme::Assign,
+                    ',' => Lexeme::Comma,
                     _ => {
-                        return Err(format!("Unexpected char: '{0}' at position {1}.", ch, char_index));
+                        return Err(LexError::UnexpectedChar{ch, span: Span::new(char_index, char_index + ch.len_utf8())});
                     }
                 }
+            };
+
+            tokens.push(Token{lexeme, span: Span::new(char_index, offset(&mut chars, source))});
+        }
+
+        Ok(tokens)
+    }
+
+    /// Lexes `source` for the integer mode, where number literals must be
+    /// whole numbers that fit in an `i128`
+    pub fn lex_integers(source: &str) -> Result<Vec<Token>, LexError> {
+        let tokens = Lexer::lex(source)?;
+
+        for token in &tokens {
+            if let Lexeme::Number(text) = &token.lexeme {
+                let (radix, digits) = split_radix(text);
+
+                if !digits.chars().all(|c| c.is_digit(radix)) {
+                    return Err(LexError::NotAnInteger{text: text.clone(), span: token.span});
+                }
+
+                if i128::from_str_radix(digits, radix).is_err() {
+                    return Err(LexError::IntegerOverflow{text: text.clone(), span: token.span});
+                }
             }
         }
 
-        return Ok(lexemes);
+        Ok(tokens)
     }
 }
 
+/// Binary operators carry both operands, unary operators (`UnaryPlus`,
+/// `UnaryMinus`) only carry the `right` one and `Call` nodes keep their
+/// arguments in `args`. A `Def` node has the `Call` shaped signature on
+/// the `left` and the function body on the `right`. `Error` nodes mark the
+/// parts of a partially parsed input that had syntax errors, whatever could
+/// be recovered around them is kept in `args`. The `span` of a node covers
+/// the whole subexpression it was parsed from.
+#[derive(Clone, Debug)]
 pub struct ASTNode {
     pub node: Lexeme,
     pub left: Option<Box<ASTNode>>,
-    pub right: Option<Box<ASTNode>>
+    pub right: Option<Box<ASTNode>>,
+    pub args: Vec<ASTNode>,
+    pub span: Span
+}
+
+impl ASTNode {
+    pub fn leaf(node: Lexeme, span: Span) -> ASTNode {
+        ASTNode{node, left: None, right: None, args: Vec::new(), span}
+    }
+
+    /// `op` is the span of the operator in front of the operand
+    pub fn unary(node: Lexeme, op: Span, operand: ASTNode) -> ASTNode {
+        let span = op.to(operand.span);
+
+        ASTNode{node, left: None, right: Some(Box::new(operand)), args: Vec::new(), span}
+    }
+
+    pub fn binary(node: Lexeme, left: ASTNode, right: ASTNode) -> ASTNode {
+        let span = left.span.to(right.span);
+
+        ASTNode{node, left: Some(Box::new(left)), right: Some(Box::new(right)), args: Vec::new(), span}
+    }
+
+    pub fn call(name: String, args: Vec<ASTNode>, span: Span) -> ASTNode {
+        ASTNode{node: Lexeme::Call(name), left: None, right: None, args, span}
+    }
+
+    pub fn error(args: Vec<ASTNode>, span: Span) -> ASTNode {
+        ASTNode{node: Lexeme::Error, left: None, right: None, args, span}
+    }
 }
 
 impl fmt::Display for ASTNode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match write!(f, "{} (", self.node) {
-            Ok(_) => (),
-            Err(e) => return Err(e)
-        };
+        write!(f, "{} (", self.node)?;
+
+        if let Lexeme::Call(_) | Lexeme::Error = self.node {
+            for (i, arg) in self.args.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+
+                write!(f, "{}", arg)?;
+            }
+
+            return write!(f, ")");
+        }
 
-        match &(*self).left {
-            Some(val) => print!("{}, ", val),
-            None => print!("_, ")
+        match &self.left {
+            Some(val) => write!(f, "{}, ", val)?,
+            None => write!(f, "_, ")?
         };
 
-        match &(*self).right {
-            Some(val) => print!("{}", val),
-            None => print!("_")
+        match &self.right {
+            Some(val) => write!(f, "{}", val)?,
+            None => write!(f, "_")?
         };
 
         write!(f, ")")
@@ -126,138 +427,773 @@ impl fmt::Display for ASTNode {
 }
 
 pub struct Parser {
-    pub lexemes: Vec<Lexeme>,
+    pub tokens: Vec<Token>,
     pub curr_lexeme: Lexeme,
-    pub curr_index: usize
+    pub curr_index: usize,
+    pub errors: Vec<ParseError>,
+    /// Bound names, they are not read as units even if they are unit names
+    pub variables: HashSet<String>
+}
+
+/// Tokens where panic-mode recovery resumes parsing after a syntax error
+fn is_synchronizing(lexeme: &Lexeme) -> bool {
+    matches!(lexeme, Lexeme::Plus | Lexeme::Minus | Lexeme::Mul | Lexeme::Div | Lexeme::Pow
+        | Lexeme::BitAnd | Lexeme::BitOr | Lexeme::BitXor | Lexeme::Shl | Lexeme::Shr | Lexeme::To
+        | Lexeme::Comma | Lexeme::Rparen(_) | Lexeme::Nil)
 }
 
 impl Parser {
-    pub fn new(lexemes: Vec<Lexeme>) -> Parser {
+    pub fn new(tokens: Vec<Token>) -> Parser {
         Parser{
-            lexemes: lexemes,
+            tokens,
             curr_lexeme: Lexeme::Nil,
-            curr_index: 0
+            curr_index: 0,
+            errors: Vec::new(),
+            variables: HashSet::new()
         }
     }
 
-    pub fn parse(&mut self) -> Result<ASTNode, String> {
+    /// Reads `variables` as the variables they are, so `g = 2` then `5 kg * g`
+    /// is 10 kg rather than 5 kg*g
+    pub fn with_variables(mut self, variables: HashSet<String>) -> Parser {
+        self.variables = variables;
+        self
+    }
+
+    /// Parses the whole input, returning every syntax error found in it
+    pub fn parse(&mut self) -> Result<ASTNode, Vec<ParseError>> {
+        let (node, errors) = self.parse_partial();
+
+        if errors.is_empty() {
+            Ok(node)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Parses the whole input even if it has syntax errors, the parts that
+    /// could not be parsed are replaced by `Error` nodes in the returned AST
+    pub fn parse_partial(&mut self) -> (ASTNode, Vec<ParseError>) {
+        let end = match self.tokens.last() {
+            Some(token) => token.span.end,
+            None => 0
+        };
+
         self.curr_index = 0;
-        self.lexemes.push(Lexeme::Nil);
+        self.errors = Vec::new();
+        self.tokens.push(Token{lexeme: Lexeme::Nil, span: Span::new(end, end)});
+
+        self.curr_lexeme = self.tokens[self.curr_index].lexeme.clone();
+
+        let mut node = self.statement();
 
-        self.curr_lexeme = self.lexemes[self.curr_index];
+        while self.curr_lexeme != Lexeme::Nil {
+            let error = self.unexpected("end of input");
+            self.report(error);
+            self.advance();
+
+            let tail = self.expr();
+            let span = node.span.to(tail.span);
+
+            node = ASTNode::error(vec![node, tail], span);
+        }
 
-        return self.expr();
+        (node, std::mem::take(&mut self.errors))
     }
 
-    fn eat(&mut self, lexeme: Lexeme) -> Option<String> {
-        if self.curr_lexeme == lexeme {
+    fn curr_span(&self) -> Span {
+        self.tokens[self.curr_index].span
+    }
+
+    /// Span from `start` up to the end of the last consumed token
+    fn span_from(&self, start: Span) -> Span {
+        match self.curr_index {
+            0 => start,
+            index => start.to(self.tokens[index - 1].span)
+        }
+    }
+
+    /// Error for the current token, `expected` describes what would have been valid
+    fn unexpected(&self, expected: &str) -> ParseError {
+        if self.curr_lexeme == Lexeme::Nil {
+            ParseError::UnexpectedEnd{expected: String::from(expected), span: self.curr_span()}
+        } else {
+            ParseError::UnexpectedToken{found: self.curr_lexeme.clone(), expected: String::from(expected), span: self.curr_span()}
+        }
+    }
+
+    /// Records a syntax error, unless it is a follow-up of the previous one
+    fn report(&mut self, error: ParseError) {
+        if let Some(last) = self.errors.last() {
+            if last.span().map(|span| span.start) == error.span().map(|span| span.start) {
+                return;
+            }
+        }
+
+        self.errors.push(error);
+    }
+
+    fn advance(&mut self) {
+        if self.curr_index + 1 < self.tokens.len() {
             self.curr_index += 1;
-            self.curr_lexeme = self.lexemes[self.curr_index];
-            None
+            self.curr_lexeme = self.tokens[self.curr_index].lexeme.clone();
+        }
+    }
+
+    /// Advances past `lexeme` if it is the current token
+    fn eat(&mut self, lexeme: Lexeme) -> bool {
+        if self.curr_lexeme == lexeme {
+            self.advance();
+            true
         } else {
-            Some(format!("Got unexpected token type: {}", lexeme))
+            false
+        }
+    }
+
+    /// Skips tokens up to the next operator, comma or closing parenthesis
+    fn synchronize(&mut self) {
+        while !is_synchronizing(&self.curr_lexeme) {
+            self.advance();
+        }
+    }
+
+    /// Eats the `Rparen` matching the `Lparen` at `open`, skipping anything
+    /// left before it
+    fn close(&mut self, level: u32, open: Span, expected: &str) {
+        if self.eat(Lexeme::Rparen(level)) {
+            return;
+        }
+
+        let error = match self.curr_lexeme {
+            Lexeme::Nil => ParseError::UnclosedParen{open, span: self.curr_span()},
+            _ => self.unexpected(expected)
+        };
+
+        self.report(error);
+
+        while self.curr_lexeme != Lexeme::Rparen(level) && self.curr_lexeme != Lexeme::Nil {
+            self.advance();
+        }
+
+        self.eat(Lexeme::Rparen(level));
+    }
+
+    fn peek(&self) -> &Lexeme {
+        self.peek_nth(1)
+    }
+
+    /// The lexeme `n` tokens after the current one
+    fn peek_nth(&self, n: usize) -> &Lexeme {
+        match self.tokens.get(self.curr_index + n) {
+            Some(token) => &token.lexeme,
+            None => &Lexeme::Nil
+        }
+    }
+
+    fn statement(&mut self) -> ASTNode {
+        let token = self.curr_lexeme.clone();
+
+        if token == Lexeme::Def {
+            return self.definition();
         }
+
+        if let Lexeme::Identifier(_) = token {
+            if *self.peek() == Lexeme::Assign {
+                let target = ASTNode::leaf(token, self.curr_span());
+
+                self.advance();
+                self.advance();
+
+                return ASTNode::binary(Lexeme::Assign, target, self.conversion());
+            }
+        }
+
+        self.conversion()
     }
 
-    fn factor(&mut self) -> Result<ASTNode, String>  {
-        let token = self.curr_lexeme;
+    fn factor(&mut self) -> ASTNode {
+        let token = self.curr_lexeme.clone();
+        let start = self.curr_span();
 
         match token {
-            Lexeme::Number(val) => {
-                match self.eat(Lexeme::Number(val)) {
-                    Some(e) => return Err(e),
-                    None => ()
-                };
-    
-                Ok(ASTNode{node: Lexeme::Number(val), left: None, right: None})
+            Lexeme::Identifier(name) => {
+                self.advance();
+
+                match self.curr_lexeme {
+                    Lexeme::Lparen(level) => {
+                        let args = self.arguments(level);
+                        ASTNode::call(name, args, self.span_from(start))
+                    },
+                    _ => ASTNode::leaf(Lexeme::Identifier(name), start)
+                }
+            },
+            Lexeme::Number(_) | Lexeme::OutRef(_) => {
+                self.advance();
+
+                ASTNode::leaf(token, start)
             },
             Lexeme::Lparen(level) => {
-                match self.eat(Lexeme::Lparen(level)) {
-                    Some(e) => return Err(e),
-                    None => ()
-                }
-    
-                let node = match self.expr() {
-                    Ok(val) => val,
-                    Err(e) => return Err(e)
-                };
-    
-                match self.eat(Lexeme::Rparen(level)) {
-                    Some(e) => return Err(e),
-                    None => ()
-                };
+                self.advance();
+
+                let mut node = self.conversion();
 
-                Ok(node)
+                self.close(level, start, "RPAREN");
+
+                // The parentheses belong to the subexpression
+                node.span = self.span_from(start);
+                node
             },
-            _ => Err(format!("Unexpected token: {}, was expecting NUMBER or LPAREN.", self.curr_lexeme))
+            _ => {
+                let error = self.unexpected("NUMBER, IDENT, OUTREF or LPAREN");
+                self.report(error);
+                self.synchronize();
+
+                ASTNode::error(Vec::new(), start)
+            }
         }
     }
 
-    fn term(&mut self) -> Result<ASTNode, String>  {
-        let mut node = match self.factor() {
-            Ok(val) => val,
-            Err(s) => return Err(s)
+    fn definition(&mut self) -> ASTNode {
+        self.advance();
+
+        let start = self.curr_span();
+        let name = match self.curr_lexeme.clone() {
+            Lexeme::Identifier(name) => {
+                self.advance();
+                Some(name)
+            },
+            _ => {
+                let error = self.unexpected("the function name");
+                self.report(error);
+                None
+            }
+        };
+
+        let params = match (&name, self.curr_lexeme.clone()) {
+            (Some(_), Lexeme::Lparen(level)) => {
+                let params = self.parameters(level);
+
+                // Parameters are variables in the body
+                for param in &params {
+                    if let Lexeme::Identifier(name) = &param.node {
+                        self.variables.insert(name.clone());
+                    }
+                }
+
+                Some(params)
+            },
+            (Some(_), _) => {
+                let error = self.unexpected("LPAREN");
+                self.report(error);
+                None
+            },
+            _ => None
         };
 
-        loop {
-            let token = self.curr_lexeme;
+        if self.curr_lexeme != Lexeme::Assign {
+            let error = self.unexpected("Assign(=)");
+            self.report(error);
 
-            match token {
-                Lexeme::Mul => match self.eat(Lexeme::Mul) {
-                    Some(e) => return Err(e),
-                    None => ()
-                },
-                Lexeme::Div => match self.eat(Lexeme::Div) {
-                    Some(e) => return Err(e),
-                    None => ()
+            while self.curr_lexeme != Lexeme::Assign && self.curr_lexeme != Lexeme::Nil {
+                self.advance();
+            }
+        }
+
+        let signature = self.span_from(start);
+
+        let body = if self.eat(Lexeme::Assign) {
+            self.conversion()
+        } else {
+            ASTNode::error(Vec::new(), self.curr_span())
+        };
+
+        match (name, params) {
+            (Some(name), Some(params)) => ASTNode::binary(Lexeme::Def, ASTNode::call(name, params, signature), body),
+            _ => {
+                let span = start.to(body.span);
+                ASTNode::error(vec![body], span)
+            }
+        }
+    }
+
+    /// Parses the parenthesized, comma separated parameter names of a definition
+    fn parameters(&mut self, level: u32) -> Vec<ASTNode> {
+        let mut params = Vec::new();
+        let open = self.curr_span();
+
+        self.advance();
+
+        if self.eat(Lexeme::Rparen(level)) {
+            return params;
+        }
+
+        loop {
+            match self.curr_lexeme.clone() {
+                Lexeme::Identifier(name) if matches!(self.peek(), Lexeme::Comma | Lexeme::Rparen(_)) => {
+                    params.push(ASTNode::leaf(Lexeme::Identifier(name), self.curr_span()));
+                    self.advance();
                 },
-                _ => break
+                Lexeme::Nil => break,
+                found => {
+                    self.report(ParseError::InvalidParameter{found, span: self.curr_span()});
+
+                    while !matches!(self.curr_lexeme, Lexeme::Comma | Lexeme::Nil) && self.curr_lexeme != Lexeme::Rparen(level) {
+                        self.advance();
+                    }
+                }
             }
 
-            node = ASTNode{
-                node: token.clone(),
-                left: Some(Box::new(node)),
-                right: Some(Box::new(match self.factor() {
-                    Ok(val) => val,
-                    Err(s) => return Err(s)
-                }))
-            };
+            if !self.eat(Lexeme::Comma) {
+                break;
+            }
         }
 
-        Ok(node)
+        self.close(level, open, "COMMA or RPAREN");
+
+        params
+    }
+
+    /// Parses a parenthesized, comma separated argument list
+    fn arguments(&mut self, level: u32) -> Vec<ASTNode> {
+        let mut args = Vec::new();
+        let open = self.curr_span();
+
+        self.advance();
+
+        if self.eat(Lexeme::Rparen(level)) {
+            return args;
+        }
+
+        loop {
+            args.push(self.expr());
+
+            if !self.eat(Lexeme::Comma) {
+                break;
+            }
+        }
+
+        self.close(level, open, "COMMA or RPAREN");
+
+        args
     }
 
-    fn expr(&mut self) -> Result<ASTNode, String> {
-        let mut node = match self.term() {
-            Ok(val) => val,
-            Err(s) => return Err(s)
+    fn unary(&mut self) -> ASTNode {
+        let node = match self.curr_lexeme {
+            Lexeme::Plus => Lexeme::UnaryPlus,
+            Lexeme::Minus => Lexeme::UnaryMinus,
+            Lexeme::BitNot => Lexeme::BitNot,
+            _ => return self.power()
         };
 
+        let op = self.curr_span();
+
+        self.advance();
+
+        ASTNode::unary(node, op, self.unary())
+    }
+
+    /// Exponentiation is right associative and binds tighter than a unary
+    /// operator on its left, but accepts one on its right: `-2^-1 == -(2^(-1))`
+    fn power(&mut self) -> ASTNode {
+        let node = self.factor();
+
+        if !self.eat(Lexeme::Pow) {
+            return node;
+        }
+
+        ASTNode::binary(Lexeme::Pow, node, self.unary())
+    }
+
+    /// Parses `operand (op operand)*` for the left associative binary
+    /// operators in `ops`
+    fn binary(&mut self, ops: &[Lexeme], operand: fn(&mut Parser) -> ASTNode) -> ASTNode {
+        let mut node = operand(self);
+
+        while ops.contains(&self.curr_lexeme) {
+            let token = self.curr_lexeme.clone();
+
+            self.advance();
+
+            node = ASTNode::binary(token, node, operand(self));
+        }
+
+        node
+    }
+
+    /// A unit name, unless it is called like a function, e.g. `min(1, 2)`
+    fn at_unit(&self) -> bool {
+        matches!(&self.curr_lexeme, Lexeme::Identifier(name) if self.is_unit(name))
+            && !matches!(self.peek(), Lexeme::Lparen(_))
+    }
+
+    fn is_unit(&self, name: &str) -> bool {
+        units::lookup(name).is_some() && !self.variables.contains(name)
+    }
+
+    /// Parses `UNIT (POW MINUS? NUMBER)? ((MUL | DIV) UNIT (POW MINUS? NUMBER)?)*`
+    /// into its text, e.g. `kg*m/s^2` or `m^-1`. `MUL` and `DIV` only continue the units if
+    /// a unit follows them, so `6 m / 2` divides the quantity by 2.
+    fn units(&mut self) -> String {
+        let mut text = String::new();
+
         loop {
-            let token = self.curr_lexeme;
+            if let Lexeme::Identifier(name) = self.curr_lexeme.clone() {
+                text.push_str(&name);
+                self.advance();
+            }
 
-            match token {
-                Lexeme::Plus => match self.eat(Lexeme::Plus) {
-                    Some(e) => return Err(e),
-                    None => ()
-                },
-                Lexeme::Minus => match self.eat(Lexeme::Minus) {
-                    Some(e) => return Err(e),
-                    None => ()
-                },
-                _ => break
+            if self.curr_lexeme == Lexeme::Pow {
+                let negative = *self.peek() == Lexeme::Minus;
+                let sign_len = if negative { 1 } else { 0 };
+
+                if let Lexeme::Number(power) = self.peek_nth(1 + sign_len).clone() {
+                    if power.chars().all(|c| c.is_ascii_digit()) {
+                        text.push('^');
+
+                        if negative {
+                            text.push('-');
+                        }
+
+                        text.push_str(&power);
+
+                        for _ in 0..2 + sign_len {
+                            self.advance();
+                        }
+                    }
+                }
             }
 
-            node = ASTNode{
-                node: token.clone(),
-                left: Some(Box::new(node)),
-                right: Some(Box::new(match self.term() {
-                    Ok(val) => val,
-                    Err(s) => return Err(s)
-                }))
+            let op = match self.curr_lexeme {
+                Lexeme::Mul => '*',
+                Lexeme::Div => '/',
+                _ => break
             };
+
+            match self.peek() {
+                Lexeme::Identifier(name) if self.is_unit(name) => text.push(op),
+                _ => break
+            }
+
+            self.advance();
+        }
+
+        text
+    }
+
+    /// Units after an operand apply to all of it, so `-5 degC` is minus
+    /// five degrees rather than the negation of five degrees
+    fn quantity(&mut self) -> ASTNode {
+        let node = self.unary();
+
+        if !self.at_unit() {
+            return node;
+        }
+
+        let start = self.curr_span();
+        let units = self.units();
+
+        ASTNode::unary(Lexeme::Unit(units), self.span_from(start), node)
+    }
+
+    fn term(&mut self) -> ASTNode {
+        self.binary(&[Lexeme::Mul, Lexeme::Div], Parser::quantity)
+    }
+
+    fn sum(&mut self) -> ASTNode {
+        self.binary(&[Lexeme::Plus, Lexeme::Minus], Parser::term)
+    }
+
+    fn shift(&mut self) -> ASTNode {
+        self.binary(&[Lexeme::Shl, Lexeme::Shr], Parser::sum)
+    }
+
+    fn bit_and(&mut self) -> ASTNode {
+        self.binary(&[Lexeme::BitAnd], Parser::shift)
+    }
+
+    fn bit_xor(&mut self) -> ASTNode {
+        self.binary(&[Lexeme::BitXor], Parser::bit_and)
+    }
+
+    /// Bitwise operators bind looser than arithmetic like in C, so
+    /// `1 << 2 + 1 == 1 << 3` and `6 & 3 | 8 == (6 & 3) | 8`
+    fn expr(&mut self) -> ASTNode {
+        self.binary(&[Lexeme::BitOr], Parser::bit_xor)
+    }
+
+    /// A conversion applies to the whole expression before it, so it can
+    /// only end a statement or a parenthesized subexpression
+    fn conversion(&mut self) -> ASTNode {
+        let node = self.expr();
+
+        if !self.eat(Lexeme::To) {
+            return node;
+        }
+
+        if !self.at_unit() {
+            let error = self.unexpected("UNIT");
+            self.report(error);
+            self.synchronize();
+
+            let span = self.span_from(node.span);
+            return ASTNode::error(vec![node], span);
         }
 
-        Ok(node)
+        let start = self.curr_span();
+        let units = self.units();
+
+        ASTNode::unary(Lexeme::Convert(units), self.span_from(start), node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Result<ASTNode, Vec<ParseError>> {
+        match Lexer::lex(source) {
+            Ok(tokens) => Parser::new(tokens).parse(),
+            Err(e) => panic!("Could not lex '{}': {}", source, e)
+        }
+    }
+
+    fn dump(source: &str) -> String {
+        match parse(source) {
+            Ok(ast) => format!("{}", ast),
+            Err(e) => panic!("Could not parse '{}': {}", source, e[0])
+        }
+    }
+
+    #[test]
+    fn unary_minus_on_number() {
+        assert_eq!(dump("-3"), "UnaryMinus(-) (_, Number(3) (_, _))");
+    }
+
+    #[test]
+    fn unary_operators_nest() {
+        assert_eq!(dump("-+-1"), "UnaryMinus(-) (_, UnaryPlus(+) (_, UnaryMinus(-) (_, Number(1) (_, _))))");
+    }
+
+    #[test]
+    fn unary_binds_tighter_than_binary_operators() {
+        assert_eq!(dump("-3 + 4"), "Plus(+) (UnaryMinus(-) (_, Number(3) (_, _)), Number(4) (_, _))");
+        assert_eq!(dump("2 * -1"), "Mul(*) (Number(2) (_, _), UnaryMinus(-) (_, Number(1) (_, _)))");
+    }
+
+    #[test]
+    fn binary_minus_is_still_binary() {
+        assert_eq!(dump("3 - -4"), "Minus(-) (Number(3) (_, _), UnaryMinus(-) (_, Number(4) (_, _)))");
+    }
+
+    #[test]
+    fn power_is_right_associative() {
+        assert_eq!(dump("2^3^2"), "Pow(^) (Number(2) (_, _), Pow(^) (Number(3) (_, _), Number(2) (_, _)))");
+        assert_eq!(dump("2**3**2"), dump("2^3^2"));
+    }
+
+    #[test]
+    fn power_binds_tighter_than_unary_and_term() {
+        assert_eq!(dump("-2^2"), "UnaryMinus(-) (_, Pow(^) (Number(2) (_, _), Number(2) (_, _)))");
+        assert_eq!(dump("2^-1"), "Pow(^) (Number(2) (_, _), UnaryMinus(-) (_, Number(1) (_, _)))");
+        assert_eq!(dump("3*2^2"), "Mul(*) (Number(3) (_, _), Pow(^) (Number(2) (_, _), Number(2) (_, _)))");
+    }
+
+    #[test]
+    fn function_calls() {
+        assert_eq!(dump("sqrt(2)"), "Call(sqrt) (Number(2) (_, _))");
+        assert_eq!(dump("max(1, x, (2))"), "Call(max) (Number(1) (_, _), Identifier(x) (_, _), Number(2) (_, _))");
+        assert_eq!(dump("f()"), "Call(f) ()");
+        assert_eq!(dump("-abs(1)^2"), "UnaryMinus(-) (_, Pow(^) (Call(abs) (Number(1) (_, _)), Number(2) (_, _)))");
+    }
+
+    #[test]
+    fn malformed_function_calls() {
+        assert!(parse("max(1, )").is_err());
+        assert!(parse("max(1 2)").is_err());
+        assert!(parse("max(1, 2").is_err());
+    }
+
+    #[test]
+    fn function_definitions() {
+        assert_eq!(dump("def hyp(a, b) = sqrt(a^2 + b^2)"),
+            "Def (Call(hyp) (Identifier(a) (_, _), Identifier(b) (_, _)), Call(sqrt) (Plus(+) (Pow(^) (Identifier(a) (_, _), Number(2) (_, _)), Pow(^) (Identifier(b) (_, _), Number(2) (_, _)))))");
+        assert_eq!(dump("def one() = 1"), "Def (Call(one) (), Number(1) (_, _))");
+    }
+
+    #[test]
+    fn malformed_function_definitions() {
+        assert!(parse("def f(1) = 2").is_err());
+        assert!(parse("def f(a + b) = 2").is_err());
+        assert!(parse("def f(a)").is_err());
+        assert!(parse("def = 2").is_err());
+    }
+
+    #[test]
+    fn float_literals_keep_their_text() {
+        assert_eq!(dump("3.14"), "Number(3.14) (_, _)");
+        assert_eq!(dump(".5"), "Number(.5) (_, _)");
+        assert_eq!(dump("1e-9"), "Number(1e-9) (_, _)");
+        assert_eq!(dump("6.02E23"), "Number(6.02E23) (_, _)");
+    }
+
+    #[test]
+    fn malformed_float_literals() {
+        assert_eq!(Lexer::lex("1 + 1.2.3").err(), Some(LexError::MalformedNumber{text: String::from("1.2."), span: Span::new(4, 8)}));
+        assert_eq!(Lexer::lex("1e").err(), Some(LexError::MissingExponent{text: String::from("1e"), span: Span::new(0, 2)}));
+        assert!(Lexer::lex("2e+").is_err());
+        assert!(Lexer::lex(".").is_err());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn lex_errors_keep_their_messages() {
+        assert_eq!(Lexer::lex("1 + 1.2.3").unwrap_err().to_string(), "Malformed number literal: '1.2.' at position 4.");
+        assert_eq!(Lexer::lex("1e").unwrap_err().to_string(), "Malformed number literal: '1e' at position 0, exponent has no digits.");
+        assert_eq!(Lexer::lex("2 $ 3").unwrap_err().to_string(), "Unexpected char: '$' at position 2.");
+    }
+
+    #[test]
+    fn token_spans_are_byte_offsets() {
+        let spans : Vec<Span> = Lexer::lex("é + foo**2").unwrap().iter().map(|token| token.span).collect();
+
+        assert_eq!(Lexer::lex("é").unwrap()[0].lexeme, Lexeme::Identifier(String::from("é")));
+        assert_eq!(spans, vec![Span::new(0, 2), Span::new(3, 4), Span::new(5, 8), Span::new(8, 10), Span::new(10, 11)]);
+    }
+
+    #[test]
+    fn unbalanced_parens() {
+        assert_eq!(Lexer::lex("1 + 2)").err(), Some(LexError::UnbalancedParen{span: Span::new(5, 6)}));
+        assert_eq!(parse("(1 + (2)").err(), Some(vec![ParseError::UnclosedParen{open: Span::new(0, 1), span: Span::new(8, 8)}]));
+    }
+
+    #[test]
+    fn parse_errors_carry_spans() {
+        assert_eq!(parse("1 + * 2").err(), Some(vec![ParseError::UnexpectedToken{
+            found: Lexeme::Mul,
+            expected: String::from("NUMBER, IDENT, OUTREF or LPAREN"),
+            span: Span::new(4, 5)
+        }]));
+        assert_eq!(parse("def f(a, 1) = a").err(), Some(vec![ParseError::InvalidParameter{found: Lexeme::Number(String::from("1")), span: Span::new(9, 10)}]));
+        assert_eq!(parse("1 +").err(), Some(vec![ParseError::UnexpectedEnd{
+            expected: String::from("NUMBER, IDENT, OUTREF or LPAREN"),
+            span: Span::new(3, 3)
+        }]));
+    }
+
+    #[test]
+    fn recovery_reports_every_syntax_error() {
+        let spans : Vec<Span> = parse("1 + * 2 - (3 4) + max(1, , 2) +").unwrap_err().iter().filter_map(|e| e.span()).collect();
+
+        assert_eq!(spans, vec![Span::new(4, 5), Span::new(13, 14), Span::new(25, 26), Span::new(31, 31)]);
+    }
+
+    #[test]
+    fn recovery_reports_trailing_tokens() {
+        let errors = parse("1 2 + * 3").unwrap_err();
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].to_string(), "Unexpected token: Number(2), was expecting end of input.");
+        assert_eq!(errors[1].span(), Some(Span::new(6, 7)));
+    }
+
+    #[test]
+    fn recovery_keeps_a_partial_ast() {
+        let (ast, errors) = Parser::new(Lexer::lex("2 * (1 + ) - sqrt(4)").unwrap()).parse_partial();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(format!("{}", ast),
+            "Minus(-) (Mul(*) (Number(2) (_, _), Plus(+) (Number(1) (_, _), <Error> ())), Call(sqrt) (Number(4) (_, _)))");
+    }
+
+    #[test]
+    fn recovery_in_definitions() {
+        let errors = parse("def f(a, 1, b + c) = a * ").unwrap_err();
+
+        assert_eq!(errors.len(), 3);
+        assert!(matches!(errors[0], ParseError::InvalidParameter{..}));
+        assert!(matches!(errors[1], ParseError::InvalidParameter{..}));
+        assert!(matches!(errors[2], ParseError::UnexpectedEnd{..}));
+    }
+
+    #[test]
+    fn dangling_unary_operator_is_an_error() {
+        assert!(parse("-").is_err());
+        assert!(parse("2 * -").is_err());
+    }
+
+    #[test]
+    fn radix_literals_and_separators() {
+        assert_eq!(dump("0xFF_ff"), "Number(0xFFff) (_, _)");
+        assert_eq!(dump("0O17"), "Number(0o17) (_, _)");
+        assert_eq!(dump("0b1010_0101"), "Number(0b10100101) (_, _)");
+        assert_eq!(dump("1_000.000_1"), "Number(1000.0001) (_, _)");
+        assert_eq!(split_radix("0x1f"), (16, "1f"));
+        assert_eq!(split_radix("10"), (10, "10"));
+    }
+
+    #[test]
+    fn malformed_radix_literals_and_separators() {
+        assert_eq!(Lexer::lex("0b102").err(), Some(LexError::MalformedNumber{text: String::from("0b102"), span: Span::new(0, 5)}));
+        assert_eq!(Lexer::lex("0x + 1").err(), Some(LexError::MalformedNumber{text: String::from("0x"), span: Span::new(0, 2)}));
+        assert_eq!(Lexer::lex("1__0").err(), Some(LexError::MalformedNumber{text: String::from("1_"), span: Span::new(0, 2)}));
+        assert!(Lexer::lex("1_").is_err());
+        assert!(Lexer::lex("0x_1").is_err());
+        assert!(Lexer::lex("1._5").is_err());
+    }
+
+    #[test]
+    fn bitwise_operators_have_c_precedence() {
+        assert_eq!(dump("1 | 2 xor 3 & 4"), "BitOr(|) (Number(1) (_, _), BitXor(xor) (Number(2) (_, _), BitAnd(&) (Number(3) (_, _), Number(4) (_, _))))");
+        assert_eq!(dump("1 << 2 + 3"), "Shl(<<) (Number(1) (_, _), Plus(+) (Number(2) (_, _), Number(3) (_, _)))");
+        assert_eq!(dump("8 >> 1 >> 1"), "Shr(>>) (Shr(>>) (Number(8) (_, _), Number(1) (_, _)), Number(1) (_, _))");
+        assert_eq!(dump("~x & 1"), "BitAnd(&) (BitNot(~) (_, Identifier(x) (_, _)), Number(1) (_, _))");
+    }
+
+    #[test]
+    fn single_angle_brackets_are_not_operators() {
+        assert_eq!(Lexer::lex("1 < 2").err(), Some(LexError::UnexpectedChar{ch: '<', span: Span::new(2, 3)}));
+        assert!(Lexer::lex("1 >< 2").is_err());
+    }
+
+    #[test]
+    fn ast_spans_cover_subexpressions() {
+        let ast = parse("(1 + 2) * -sqrt(4)").unwrap();
+
+        assert_eq!(ast.span, Span::new(0, 18));
+        assert_eq!(ast.left.unwrap().span, Span::new(0, 7));
+        assert_eq!(ast.right.as_ref().unwrap().span, Span::new(10, 18));
+        assert_eq!(ast.right.unwrap().right.unwrap().span, Span::new(11, 18));
+    }
+
+    #[test]
+    fn units_follow_their_operand() {
+        assert_eq!(dump("-5 degC"), "Unit(degC) (_, UnaryMinus(-) (_, Number(5) (_, _)))");
+        assert_eq!(dump("3 m^2"), "Unit(m^2) (_, Number(3) (_, _))");
+        assert_eq!(dump("20 km/h to m/s"), "Convert(m/s) (_, Unit(km/h) (_, Number(20) (_, _)))");
+        assert_eq!(dump("6 m / 2"), "Div(/) (Unit(m) (_, Number(6) (_, _)), Number(2) (_, _))");
+        assert_eq!(dump("x = 1 kg*m/s^2 to N"), "Assign(=) (Identifier(x) (_, _), Convert(N) (_, Unit(kg*m/s^2) (_, Number(1) (_, _))))");
+    }
+
+    #[test]
+    fn negative_unit_powers() {
+        assert_eq!(dump("3 m^-1"), "Unit(m^-1) (_, Number(3) (_, _))");
+        assert_eq!(dump("2 kg*s^-2 to N/m"), "Convert(N/m) (_, Unit(kg*s^-2) (_, Number(2) (_, _)))");
+    }
+
+    #[test]
+    fn variables_shadow_units() {
+        let variables : HashSet<String> = ["g", "s"].iter().map(|name| String::from(*name)).collect();
+        let parse_with = |source: &str| format!("{}", Parser::new(Lexer::lex(source).unwrap()).with_variables(variables.clone()).parse().unwrap());
+
+        assert_eq!(parse_with("5 kg * g"), "Mul(*) (Unit(kg) (_, Number(5) (_, _)), Identifier(g) (_, _))");
+        assert_eq!(parse_with("3 m/s"), "Div(/) (Unit(m) (_, Number(3) (_, _)), Identifier(s) (_, _))");
+        assert_eq!(dump("def f(g) = 5 kg * g"), "Def (Call(f) (Identifier(g) (_, _)), Mul(*) (Unit(kg) (_, Number(5) (_, _)), Identifier(g) (_, _)))");
+        assert_eq!(dump("5 kg * g"), "Unit(kg*g) (_, Number(5) (_, _))");
+    }
+
+    #[test]
+    fn units_are_not_function_calls() {
+        assert_eq!(dump("2 * min(1, 2)"), "Mul(*) (Number(2) (_, _), Call(min) (Number(1) (_, _), Number(2) (_, _)))");
+        assert!(parse("2 min(1, 2)").is_err());
+        assert!(parse("1 m to 2").is_err());
+    }
+}
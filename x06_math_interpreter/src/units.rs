@@ -0,0 +1,483 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{One, Pow, ToPrimitive, Zero};
+
+use crate::error::EvalError;
+use crate::parser::Span;
+use crate::value::{BinaryOp, Value};
+
+const BASE_UNITS: [&str; 7] = ["m", "kg", "s", "A", "K", "mol", "cd"];
+
+/// Exponents of the SI base units, in the order of `BASE_UNITS`
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Dimension([i8; 7]);
+
+const NONE: Dimension = Dimension([0, 0, 0, 0, 0, 0, 0]);
+const LENGTH: Dimension = Dimension([1, 0, 0, 0, 0, 0, 0]);
+const MASS: Dimension = Dimension([0, 1, 0, 0, 0, 0, 0]);
+const TIME: Dimension = Dimension([0, 0, 1, 0, 0, 0, 0]);
+const CURRENT: Dimension = Dimension([0, 0, 0, 1, 0, 0, 0]);
+const TEMPERATURE: Dimension = Dimension([0, 0, 0, 0, 1, 0, 0]);
+const AMOUNT: Dimension = Dimension([0, 0, 0, 0, 0, 1, 0]);
+const LUMINOSITY: Dimension = Dimension([0, 0, 0, 0, 0, 0, 1]);
+const VOLUME: Dimension = Dimension([3, 0, 0, 0, 0, 0, 0]);
+const SPEED: Dimension = Dimension([1, 0, -1, 0, 0, 0, 0]);
+const FREQUENCY: Dimension = Dimension([0, 0, -1, 0, 0, 0, 0]);
+const FORCE: Dimension = Dimension([1, 1, -2, 0, 0, 0, 0]);
+const ENERGY: Dimension = Dimension([2, 1, -2, 0, 0, 0, 0]);
+const POWER: Dimension = Dimension([2, 1, -3, 0, 0, 0, 0]);
+const PRESSURE: Dimension = Dimension([-1, 1, -2, 0, 0, 0, 0]);
+const CHARGE: Dimension = Dimension([0, 0, 1, 1, 0, 0, 0]);
+const VOLTAGE: Dimension = Dimension([2, 1, -3, -1, 0, 0, 0]);
+const RESISTANCE: Dimension = Dimension([2, 1, -3, -2, 0, 0, 0]);
+
+impl Dimension {
+    pub fn is_none(&self) -> bool {
+        *self == NONE
+    }
+
+    /// Dimension of a product for a `sign` of 1, of a quotient for -1,
+    /// `None` if an exponent gets out of range
+    fn combine(self, other: Dimension, sign: i8) -> Option<Dimension> {
+        let mut exponents = self.0;
+
+        for (exponent, other) in exponents.iter_mut().zip(other.0.iter()) {
+            *exponent = exponent.checked_add(other.checked_mul(sign)?)?;
+        }
+
+        Some(Dimension(exponents))
+    }
+
+    fn pow(self, power: i32) -> Option<Dimension> {
+        let mut exponents = self.0;
+
+        for exponent in exponents.iter_mut() {
+            *exponent = i8::try_from((*exponent as i32).checked_mul(power)?).ok()?;
+        }
+
+        Some(Dimension(exponents))
+    }
+}
+
+/// Writes the dimension in SI base units, e.g. `m*kg/s^2`, `1/s` or `1`
+impl fmt::Display for Dimension {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let part = |name: &str, exponent: i8| match exponent {
+            1 => String::from(name),
+            _ => format!("{}^{}", name, exponent)
+        };
+
+        let numer : Vec<String> = BASE_UNITS.iter().zip(self.0.iter())
+            .filter(|(_, exponent)| **exponent > 0)
+            .map(|(name, exponent)| part(name, *exponent))
+            .collect();
+
+        if numer.is_empty() {
+            write!(f, "1")?;
+        } else {
+            write!(f, "{}", numer.join("*"))?;
+        }
+
+        for (name, exponent) in BASE_UNITS.iter().zip(self.0.iter()) {
+            if *exponent < 0 {
+                write!(f, "/{}", part(name, -exponent))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A unit is `factor` times the SI base units of `dims`, offset units also
+/// move the zero point: `x degC` is `x + 273.15 K`
+pub struct Unit {
+    pub name: &'static str,
+    factor: (i64, i64),
+    dims: Dimension,
+    offset: Option<(i64, i64)>
+}
+
+const fn unit(name: &'static str, numer: i64, denom: i64, dims: Dimension) -> Unit {
+    Unit{name, factor: (numer, denom), dims, offset: None}
+}
+
+pub static UNITS: &[Unit] = &[
+    unit("m", 1, 1, LENGTH),
+    unit("km", 1000, 1, LENGTH),
+    unit("cm", 1, 100, LENGTH),
+    unit("mm", 1, 1000, LENGTH),
+    unit("um", 1, 1_000_000, LENGTH),
+    unit("nm", 1, 1_000_000_000, LENGTH),
+    unit("in", 254, 10_000, LENGTH),
+    unit("ft", 3048, 10_000, LENGTH),
+    unit("yd", 9144, 10_000, LENGTH),
+    unit("mi", 1_609_344, 1000, LENGTH),
+    unit("nmi", 1852, 1, LENGTH),
+    unit("kg", 1, 1, MASS),
+    unit("g", 1, 1000, MASS),
+    unit("mg", 1, 1_000_000, MASS),
+    unit("t", 1000, 1, MASS),
+    unit("lb", 45_359_237, 100_000_000, MASS),
+    unit("oz", 45_359_237, 1_600_000_000, MASS),
+    unit("s", 1, 1, TIME),
+    unit("ms", 1, 1000, TIME),
+    unit("us", 1, 1_000_000, TIME),
+    unit("ns", 1, 1_000_000_000, TIME),
+    unit("min", 60, 1, TIME),
+    unit("h", 3600, 1, TIME),
+    unit("d", 86_400, 1, TIME),
+    unit("A", 1, 1, CURRENT),
+    unit("mA", 1, 1000, CURRENT),
+    unit("K", 1, 1, TEMPERATURE),
+    Unit{name: "degC", factor: (1, 1), dims: TEMPERATURE, offset: Some((27_315, 100))},
+    Unit{name: "degF", factor: (5, 9), dims: TEMPERATURE, offset: Some((45_967, 180))},
+    unit("degR", 5, 9, TEMPERATURE),
+    unit("deltaC", 1, 1, TEMPERATURE),
+    unit("deltaF", 5, 9, TEMPERATURE),
+    unit("mol", 1, 1, AMOUNT),
+    unit("cd", 1, 1, LUMINOSITY),
+    unit("L", 1, 1000, VOLUME),
+    unit("mL", 1, 1_000_000, VOLUME),
+    unit("gal", 3_785_411_784, 1_000_000_000_000, VOLUME),
+    unit("mph", 44_704, 100_000, SPEED),
+    unit("kn", 1852, 3600, SPEED),
+    unit("Hz", 1, 1, FREQUENCY),
+    unit("kHz", 1000, 1, FREQUENCY),
+    unit("MHz", 1_000_000, 1, FREQUENCY),
+    unit("GHz", 1_000_000_000, 1, FREQUENCY),
+    unit("N", 1, 1, FORCE),
+    unit("kN", 1000, 1, FORCE),
+    unit("lbf", 44_482_216_152_605, 10_000_000_000_000, FORCE),
+    unit("J", 1, 1, ENERGY),
+    unit("kJ", 1000, 1, ENERGY),
+    unit("cal", 4184, 1000, ENERGY),
+    unit("kcal", 4184, 1, ENERGY),
+    unit("Wh", 3600, 1, ENERGY),
+    unit("kWh", 3_600_000, 1, ENERGY),
+    unit("W", 1, 1, POWER),
+    unit("kW", 1000, 1, POWER),
+    unit("Pa", 1, 1, PRESSURE),
+    unit("kPa", 1000, 1, PRESSURE),
+    unit("bar", 100_000, 1, PRESSURE),
+    unit("atm", 101_325, 1, PRESSURE),
+    unit("psi", 44_482_216_152_605, 6_451_600_000, PRESSURE),
+    unit("C", 1, 1, CHARGE),
+    unit("V", 1, 1, VOLTAGE),
+    unit("ohm", 1, 1, RESISTANCE)
+];
+
+pub fn lookup(name: &str) -> Option<&'static Unit> {
+    UNITS.iter().find(|unit| unit.name == name)
+}
+
+fn ratio((numer, denom): (i64, i64)) -> BigRational {
+    BigRational::new(BigInt::from(numer), BigInt::from(denom))
+}
+
+/// A unit expression like `km/h` or `m^2`, resolved to SI base units
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnitExpr {
+    pub text: String,
+    factor: BigRational,
+    dims: Dimension,
+    offset: Option<BigRational>
+}
+
+/// Resolves a unit expression as the parser writes it, e.g. `kg*m/s^2`.
+/// Offset units only make sense on their own, so `degC/s` is an error.
+pub fn resolve(text: &str, span: Span) -> Result<UnitExpr, EvalError> {
+    let invalid = || EvalError::InvalidUnit{unit: String::from(text), span};
+
+    let mut factor = BigRational::one();
+    let mut dims = NONE;
+    let mut offset = None;
+    let mut count = 0;
+    let mut sign = 1;
+    let mut rest = text;
+
+    loop {
+        let end = rest.find(['*', '/']).unwrap_or(rest.len());
+        let (part, next) = rest.split_at(end);
+
+        let (name, power) = match part.split_once('^') {
+            Some((name, power)) => (name, power.parse::<i32>().map_err(|_| invalid())?),
+            None => (part, 1)
+        };
+
+        let unit = lookup(name).ok_or_else(invalid)?;
+        let power = power * sign;
+
+        if let Some(val) = unit.offset {
+            offset = Some(ratio(val));
+        }
+
+        factor *= Pow::pow(ratio(unit.factor), power);
+        dims = unit.dims.pow(power).and_then(|unit_dims| dims.combine(unit_dims, 1)).ok_or_else(invalid)?;
+        count += 1;
+
+        sign = match next.chars().next() {
+            Some('*') => 1,
+            Some('/') => -1,
+            _ => break
+        };
+
+        rest = &next[1..];
+    }
+
+    if offset.is_some() && (count > 1 || dims != TEMPERATURE) {
+        return Err(invalid());
+    }
+
+    Ok(UnitExpr{text: String::from(text), factor, dims, offset})
+}
+
+/// A number with a physical dimension
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quantity {
+    /// The number in `unit`
+    pub value: Value,
+    pub dims: Dimension,
+    /// An absolute temperature, i.e. a point on a temperature scale rather
+    /// than a difference. See `binary` for what can be done with them.
+    pub absolute: bool,
+    /// SI base units if `None`
+    pub unit: Option<UnitExpr>
+}
+
+impl Quantity {
+    /// The number and the unit the quantity is shown with
+    pub fn parts(&self) -> (Value, String) {
+        match &self.unit {
+            Some(unit) => (self.value.clone(), unit.text.clone()),
+            None => (self.value.clone(), self.dims.to_string())
+        }
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (value, unit) = self.parts();
+
+        write!(f, "{} {}", value, unit)
+    }
+}
+
+/// Plain numbers for dimensionless results, quantities otherwise
+fn quantity(value: Value, dims: Dimension, absolute: bool, unit: Option<UnitExpr>) -> Value {
+    if dims.is_none() && !absolute {
+        value
+    } else {
+        Value::Quantity(Box::new(Quantity{value, dims, absolute, unit}))
+    }
+}
+
+/// Number, dimension, absolute flag and unit of any value
+fn split(value: Value) -> (Value, Dimension, bool, Option<UnitExpr>) {
+    match value {
+        Value::Quantity(val) => (val.value, val.dims, val.absolute, val.unit),
+        val => (val, NONE, false, None)
+    }
+}
+
+/// Converts a number in `from` to a number in `to`, `None` being SI base
+/// units. Offsets only apply to absolute temperatures, a difference of
+/// 9 degF is 5 K. Floats are converted exactly and rounded once, so that
+/// `-40 degC to degF` gives -40 rather than something close to it.
+fn rescale(value: Value, from: Option<&UnitExpr>, to: Option<&UnitExpr>, absolute: bool, span: Span) -> Result<Value, EvalError> {
+    let parts = |unit: Option<&UnitExpr>| match unit {
+        Some(unit) => (unit.factor.clone(), unit.offset.clone().filter(|_| absolute).unwrap_or_else(BigRational::zero)),
+        None => (BigRational::one(), BigRational::zero())
+    };
+
+    let (from_factor, from_offset) = parts(from);
+    let (to_factor, to_offset) = parts(to);
+
+    let offset = (from_offset - to_offset) / &to_factor;
+    let factor = from_factor / to_factor;
+
+    if factor.is_one() && offset.is_zero() {
+        return Ok(value);
+    }
+
+    match value {
+        Value::Float(val) => match BigRational::from_float(val) {
+            Some(exact) => match (exact * factor + offset).to_f64() {
+                Some(val) if val.is_finite() => Ok(Value::Float(val)),
+                _ => Err(EvalError::Overflow{span})
+            },
+            // Factors are positive, so infinities and NaN stay as they are
+            None => Ok(Value::Float(val))
+        },
+        // Integers stay integers if the result has no fractional part
+        Value::Int(val) => {
+            let exact = BigRational::from_integer(BigInt::from(val)) * factor + offset;
+
+            match exact.to_integer().to_i128() {
+                Some(val) if exact.is_integer() => Ok(Value::Int(val)),
+                _ => Ok(Value::Exact(exact))
+            }
+        },
+        val => {
+            let scaled = Value::binary(BinaryOp::Mul, val, Value::Exact(factor), span)?;
+            Value::binary(BinaryOp::Add, scaled, Value::Exact(offset), span)
+        }
+    }
+}
+
+/// Absolute temperatures, `value` read in `unit`, cannot be below 0 K
+fn check_absolute_zero(value: &Value, unit: Option<&UnitExpr>, span: Span) -> Result<(), EvalError> {
+    if rescale(value.clone(), unit, None, true, span)?.to_f64() < 0.0 {
+        return Err(EvalError::BelowAbsoluteZero{span});
+    }
+
+    Ok(())
+}
+
+/// `value unit`, e.g. `20 km/h` or `-5 degC`. An offset unit reads the
+/// number as an absolute temperature, so it only applies to plain numbers.
+pub fn apply(value: Value, unit: UnitExpr, span: Span) -> Result<Value, EvalError> {
+    if let Value::Quantity(_) = value {
+        let one = quantity(Value::Int(1), unit.dims, unit.offset.is_some(), Some(unit));
+        return binary(BinaryOp::Mul, value, one, span);
+    }
+
+    let absolute = unit.offset.is_some();
+
+    if absolute {
+        check_absolute_zero(&value, Some(&unit), span)?;
+    }
+
+    Ok(quantity(value, unit.dims, absolute, Some(unit)))
+}
+
+/// `value to unit`, the result is shown in `unit`. Converting to an offset
+/// unit reads the value as an absolute temperature, use `deltaC` or
+/// `deltaF` for temperature differences.
+pub fn convert(value: Value, unit: UnitExpr, span: Span) -> Result<Value, EvalError> {
+    let (value, dims, absolute, from) = split(value);
+
+    if dims != unit.dims {
+        return Err(EvalError::DimensionMismatch{lhs: dims.to_string(), rhs: unit.dims.to_string(), span});
+    }
+
+    let absolute = absolute || unit.offset.is_some();
+
+    if absolute {
+        check_absolute_zero(&value, from.as_ref(), span)?;
+    }
+
+    let value = rescale(value, from.as_ref(), Some(&unit), absolute, span)?;
+
+    Ok(Value::Quantity(Box::new(Quantity{value, dims, absolute, unit: Some(unit)})))
+}
+
+/// Arithmetic where at least one side is a quantity. Absolute temperatures
+/// can be moved by a difference (`20 degC + 5 deltaC`) or subtracted from
+/// each other (`20 degC - 5 degC == 15 K`), anything else done with them
+/// depends on the scale they were read on and is an error.
+pub fn binary(op: BinaryOp, lhs: Value, rhs: Value, span: Span) -> Result<Value, EvalError> {
+    let (lhs, lhs_dims, lhs_absolute, lhs_unit) = split(lhs);
+    let (rhs, rhs_dims, rhs_absolute, rhs_unit) = split(rhs);
+
+    match op {
+        BinaryOp::Add | BinaryOp::Sub => {
+            if lhs_dims != rhs_dims {
+                return Err(EvalError::DimensionMismatch{lhs: lhs_dims.to_string(), rhs: rhs_dims.to_string(), span});
+            }
+
+            let absolute = match (op, lhs_absolute, rhs_absolute) {
+                (BinaryOp::Sub, true, true) => false,
+                (_, true, true) | (BinaryOp::Sub, false, true) => return Err(EvalError::OffsetArithmetic{span}),
+                (_, lhs_absolute, rhs_absolute) => lhs_absolute || rhs_absolute
+            };
+
+            // Computes in the unit of the absolute temperature if there is
+            // one, in the unit of the left side otherwise
+            let unit = if rhs_absolute && !lhs_absolute { rhs_unit.clone() } else { lhs_unit.clone().or_else(|| rhs_unit.clone()) };
+
+            let lhs = rescale(lhs, lhs_unit.as_ref(), unit.as_ref(), lhs_absolute, span)?;
+            let rhs = rescale(rhs, rhs_unit.as_ref(), unit.as_ref(), rhs_absolute, span)?;
+            let result = Value::binary(op, lhs, rhs, span)?;
+
+            match unit {
+                // A difference read on an offset scale is shown in kelvin
+                Some(unit) if unit.offset.is_some() && !absolute => {
+                    Ok(quantity(rescale(result, Some(&unit), None, false, span)?, lhs_dims, false, None))
+                },
+                unit => {
+                    if absolute {
+                        check_absolute_zero(&result, unit.as_ref(), span)?;
+                    }
+
+                    Ok(quantity(result, lhs_dims, absolute, unit))
+                }
+            }
+        },
+        _ if lhs_absolute || rhs_absolute => Err(EvalError::OffsetArithmetic{span}),
+        // Scaling by a plain number keeps the unit, `2 * 3 km == 6 km`
+        BinaryOp::Mul | BinaryOp::Div if rhs_dims.is_none() => Ok(quantity(Value::binary(op, lhs, rhs, span)?, lhs_dims, false, lhs_unit)),
+        BinaryOp::Mul if lhs_dims.is_none() => Ok(quantity(Value::binary(op, lhs, rhs, span)?, rhs_dims, false, rhs_unit)),
+        BinaryOp::Mul | BinaryOp::Div => {
+            let sign = if op == BinaryOp::Mul { 1 } else { -1 };
+            let dims = lhs_dims.combine(rhs_dims, sign).ok_or(EvalError::Overflow{span})?;
+
+            let lhs = rescale(lhs, lhs_unit.as_ref(), None, false, span)?;
+            let rhs = rescale(rhs, rhs_unit.as_ref(), None, false, span)?;
+
+            Ok(quantity(Value::binary(op, lhs, rhs, span)?, dims, false, None))
+        },
+        BinaryOp::Pow => {
+            if !rhs_dims.is_none() {
+                return Err(EvalError::DimensionMismatch{lhs: String::from("1"), rhs: rhs_dims.to_string(), span});
+            }
+
+            let power = rhs.integral().and_then(|val| val.to_i32()).ok_or(EvalError::FractionalPower{span})?;
+            let dims = lhs_dims.pow(power).ok_or(EvalError::Overflow{span})?;
+            let lhs = rescale(lhs, lhs_unit.as_ref(), None, false, span)?;
+
+            Ok(quantity(Value::binary(op, lhs, rhs, span)?, dims, false, None))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolved(text: &str) -> UnitExpr {
+        resolve(text, Span::new(0, 0)).unwrap()
+    }
+
+    #[test]
+    fn unit_expressions_resolve_to_base_units() {
+        let speed = resolved("km/h");
+        assert_eq!((speed.factor, speed.dims), (ratio((5, 18)), SPEED));
+
+        assert_eq!(resolved("kg*m/s^2").dims, FORCE);
+        assert_eq!(resolved("cm^3").factor, ratio((1, 1_000_000)));
+        assert!(resolve("degC/s", Span::new(0, 0)).is_err());
+        assert!(resolve("degC^2", Span::new(0, 0)).is_err());
+        assert!(resolve("parsec", Span::new(0, 0)).is_err());
+    }
+
+    #[test]
+    fn dimensions_display_in_base_units() {
+        assert_eq!(FORCE.to_string(), "m*kg/s^2");
+        assert_eq!(FREQUENCY.to_string(), "1/s");
+        assert_eq!(NONE.to_string(), "1");
+        assert_eq!(LENGTH.pow(200), None);
+    }
+
+    #[test]
+    fn floats_are_rescaled_exactly() {
+        let fahrenheit = resolved("degF");
+        let celsius = resolved("degC");
+
+        assert_eq!(rescale(Value::Float(-40.0), Some(&celsius), Some(&fahrenheit), true, Span::new(0, 0)), Ok(Value::Float(-40.0)));
+        assert_eq!(rescale(Value::Float(9.0), Some(&fahrenheit), None, false, Span::new(0, 0)), Ok(Value::Float(5.0)));
+        assert_eq!(rescale(Value::Int(3), Some(&resolved("km")), None, false, Span::new(0, 0)), Ok(Value::Int(3000)));
+    }
+}
@@ -0,0 +1,465 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{FromPrimitive, One, Pow, Signed, ToPrimitive, Zero};
+
+use crate::decimal::{self, Decimal, Rounding};
+use crate::error::EvalError;
+use crate::parser::{self, Span};
+use crate::units::{self, Quantity};
+
+/// Exact mode refuses to build numbers with more bits than this, e.g. `10^10^9`
+const MAX_EXACT_BITS: u64 = 1 << 24;
+
+/// The number type literals are read as, chosen per session with `:mode`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    Float,
+    /// Big integers, with division producing exact rationals
+    Exact,
+    /// Checked `i128` arithmetic, division truncates towards zero
+    Integer,
+    /// Base-10 fixed-point numbers with `scale` digits after the decimal point
+    Decimal{scale: u32, rounding: Rounding}
+}
+
+impl Mode {
+    /// Parses the arguments of `:mode`, e.g. `["exact"]` or `["decimal", "4", "half-up"]`
+    pub fn parse(args: &[&str]) -> Result<Mode, String> {
+        match args {
+            ["float"] => Ok(Mode::Float),
+            ["exact"] => Ok(Mode::Exact),
+            ["integer"] => Ok(Mode::Integer),
+            ["decimal", options @ ..] if options.len() <= 2 => {
+                let mut scale = decimal::DEFAULT_SCALE;
+                let mut rounding = Rounding::HalfEven;
+
+                for option in options {
+                    if let Some(val) = Rounding::parse(option) {
+                        rounding = val;
+                    } else {
+                        scale = match option.parse() {
+                            Ok(val) if val <= decimal::MAX_SCALE => val,
+                            _ => return Err(format!("Invalid decimal option: '{}', expected a scale up to {} or a rounding mode", option, decimal::MAX_SCALE))
+                        };
+                    }
+                }
+
+                Ok(Mode::Decimal{scale, rounding})
+            },
+            _ => Err(format!("Unknown mode: '{}', expected 'float', 'exact', 'integer' or 'decimal'", args.join(" ")))
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mode::Float => write!(f, "float"),
+            Mode::Exact => write!(f, "exact"),
+            Mode::Integer => write!(f, "integer"),
+            Mode::Decimal{scale, rounding} => write!(f, "decimal, scale {}, {}", scale, rounding)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow
+}
+
+impl BinaryOp {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Pow => "^"
+        }
+    }
+}
+
+/// Bitwise operators, they only accept integral operands
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BitwiseOp {
+    And,
+    Or,
+    Xor,
+    Shl,
+    Shr
+}
+
+/// Result of an evaluation. Mixing number types widens to the one that can
+/// hold both operands: `Int` to `Exact`, `Exact` to `Decimal`, and anything
+/// with a `Float` to `Float`. A `Quantity` holds one of the others as its
+/// magnitude.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Float(f64),
+    Exact(BigRational),
+    Int(i128),
+    Decimal(Decimal),
+    Quantity(Box<Quantity>)
+}
+
+/// Reads a literal like `12`, `0.25` or `6.02e23` as an exact rational
+fn exact_literal(text: &str, span: Span) -> Result<BigRational, EvalError> {
+    let invalid = || EvalError::InvalidLiteral(String::from(text));
+
+    let (radix, digits) = parser::split_radix(text);
+
+    if radix != 10 {
+        return BigInt::parse_bytes(digits.as_bytes(), radix).map(BigRational::from_integer).ok_or_else(invalid);
+    }
+
+    let (mantissa, exponent) = match text.find(['e', 'E']) {
+        Some(pos) => (&text[..pos], text[pos + 1..].parse::<i64>().map_err(|_| invalid())?),
+        None => (text, 0)
+    };
+
+    let (integral, fraction) = match mantissa.find('.') {
+        Some(pos) => (&mantissa[..pos], &mantissa[pos + 1..]),
+        None => (mantissa, "")
+    };
+
+    let numer : BigInt = format!("{}{}", integral, fraction).parse().map_err(|_| invalid())?;
+    let scale = exponent - fraction.len() as i64;
+
+    // log2(10) < 4, so 10^scale needs less than 4 * scale bits
+    if scale.unsigned_abs() * 4 > MAX_EXACT_BITS {
+        return Err(EvalError::Overflow{span});
+    }
+
+    let power = Pow::pow(BigInt::from(10), scale.unsigned_abs());
+
+    if scale >= 0 {
+        Ok(BigRational::from_integer(numer * power))
+    } else {
+        Ok(BigRational::new(numer, power))
+    }
+}
+
+fn float_binary(op: BinaryOp, lhs: f64, rhs: f64, span: Span) -> Result<Value, EvalError> {
+    let result = match op {
+        BinaryOp::Add => lhs + rhs,
+        BinaryOp::Sub => lhs - rhs,
+        BinaryOp::Mul => lhs * rhs,
+        BinaryOp::Div if rhs == 0.0 => return Err(EvalError::DivisionByZero{span}),
+        BinaryOp::Div => lhs / rhs,
+        // Like a division, so that every mode agrees on `0^-1`
+        BinaryOp::Pow if lhs == 0.0 && rhs < 0.0 => return Err(EvalError::DivisionByZero{span}),
+        BinaryOp::Pow => lhs.powf(rhs)
+    };
+
+    // E.g. `(-8)^(1/3)`, powers of negative numbers are only real for
+    // integer exponents
+    if result.is_nan() && !lhs.is_nan() && !rhs.is_nan() {
+        return Err(EvalError::Domain(String::from(op.symbol())));
+    }
+
+    // Infinite results are only accepted from infinite operands
+    if result.is_infinite() && lhs.is_finite() && rhs.is_finite() {
+        Err(EvalError::Overflow{span})
+    } else {
+        Ok(Value::Float(result))
+    }
+}
+
+fn exact_pow(base: BigRational, exponent: BigRational, span: Span) -> Result<Value, EvalError> {
+    if !exponent.is_integer() {
+        return float_binary(BinaryOp::Pow, Value::Exact(base).to_f64(), Value::Exact(exponent).to_f64(), span);
+    }
+
+    if base.is_zero() && exponent.is_negative() {
+        return Err(EvalError::DivisionByZero{span});
+    }
+
+    let exponent = exponent.to_integer();
+
+    // Powers of 0, 1 and -1 never grow, whatever the exponent
+    if base.is_zero() || base.abs().is_one() {
+        let exponent = if exponent.is_zero() { 0 } else if exponent.is_even() { 2 } else { 1 };
+        return Ok(Value::Exact(Pow::pow(base, exponent)));
+    }
+
+    let bits = base.numer().bits().max(base.denom().bits());
+
+    let exponent = match exponent.to_i32() {
+        Some(val) if bits.saturating_mul(val.unsigned_abs() as u64) <= MAX_EXACT_BITS => val,
+        _ => return Err(EvalError::Overflow{span})
+    };
+
+    Ok(Value::Exact(Pow::pow(base, exponent)))
+}
+
+fn exact_bits(val: &BigRational) -> (u64, u64) {
+    (val.numer().bits(), val.denom().bits())
+}
+
+fn exact_binary(op: BinaryOp, lhs: BigRational, rhs: BigRational, span: Span) -> Result<Value, EvalError> {
+    let ((a, b), (c, d)) = (exact_bits(&lhs), exact_bits(&rhs));
+
+    // Bits of the unreduced result, checked before computing it so that
+    // repeated products cannot grow without bound like powers
+    let bits = match op {
+        BinaryOp::Add | BinaryOp::Sub => (a + d).max(c + b) + 1 + b + d,
+        BinaryOp::Mul => (a + c).max(b + d),
+        BinaryOp::Div => (a + d).max(b + c),
+        BinaryOp::Pow => 0
+    };
+
+    if bits > MAX_EXACT_BITS {
+        return Err(EvalError::Overflow{span});
+    }
+
+    // Integers skip the gcd of the rational operations, which is quadratic
+    // in the number of bits
+    if lhs.is_integer() && rhs.is_integer() {
+        let (lhs, rhs) = (lhs.numer(), rhs.numer());
+
+        match op {
+            BinaryOp::Add => return Ok(Value::Exact(BigRational::from_integer(lhs + rhs))),
+            BinaryOp::Sub => return Ok(Value::Exact(BigRational::from_integer(lhs - rhs))),
+            BinaryOp::Mul => return Ok(Value::Exact(BigRational::from_integer(lhs * rhs))),
+            _ => ()
+        }
+    }
+
+    match op {
+        BinaryOp::Add => Ok(Value::Exact(lhs + rhs)),
+        BinaryOp::Sub => Ok(Value::Exact(lhs - rhs)),
+        BinaryOp::Mul => Ok(Value::Exact(lhs * rhs)),
+        BinaryOp::Div if rhs.is_zero() => Err(EvalError::DivisionByZero{span}),
+        BinaryOp::Div => Ok(Value::Exact(lhs / rhs)),
+        BinaryOp::Pow => exact_pow(lhs, rhs, span)
+    }
+}
+
+/// Computes exactly, then rounds the result to `scale` digits
+fn decimal_binary(op: BinaryOp, lhs: BigRational, rhs: BigRational, scale: u32, rounding: Rounding, span: Span) -> Result<Value, EvalError> {
+    match exact_binary(op, lhs, rhs, span)? {
+        Value::Exact(val) => Ok(Value::Decimal(Decimal::from_rational(&val, scale, rounding))),
+        val => Ok(val)
+    }
+}
+
+/// `base^exponent` truncated towards zero like the integer division, so
+/// negative exponents give 0 unless `base` is 1 or -1
+fn int_pow(base: i128, exponent: i128, span: Span) -> Result<i128, EvalError> {
+    match (base, exponent) {
+        (0, exponent) if exponent < 0 => Err(EvalError::DivisionByZero{span}),
+        (1, _) => Ok(1),
+        (-1, exponent) => Ok(if exponent % 2 == 0 { 1 } else { -1 }),
+        (_, exponent) if exponent < 0 => Ok(0),
+        (base, exponent) => u32::try_from(exponent).ok()
+            .and_then(|exponent| base.checked_pow(exponent))
+            .ok_or(EvalError::Overflow{span})
+    }
+}
+
+fn int_binary(op: BinaryOp, lhs: i128, rhs: i128, span: Span) -> Result<Value, EvalError> {
+    let result = match op {
+        BinaryOp::Add => lhs.checked_add(rhs),
+        BinaryOp::Sub => lhs.checked_sub(rhs),
+        BinaryOp::Mul => lhs.checked_mul(rhs),
+        BinaryOp::Div if rhs == 0 => return Err(EvalError::DivisionByZero{span}),
+        BinaryOp::Div => lhs.checked_div(rhs),
+        BinaryOp::Pow => Some(int_pow(lhs, rhs, span)?)
+    };
+
+    result.map(Value::Int).ok_or(EvalError::Overflow{span})
+}
+
+impl Value {
+    /// Reads the number literal `text` as `mode` says, `span` is where it
+    /// was read from
+    pub fn from_literal(text: &str, mode: Mode, span: Span) -> Result<Value, EvalError> {
+        match mode {
+            Mode::Float if parser::split_radix(text).0 != 10 => Ok(Value::Float(exact_literal(text, span)?.to_f64().unwrap_or(f64::NAN))),
+            Mode::Float => match text.parse() {
+                Ok(val) => Ok(Value::Float(val)),
+                Err(_) => Err(EvalError::InvalidLiteral(String::from(text)))
+            },
+            Mode::Exact => exact_literal(text, span).map(Value::Exact),
+            Mode::Integer => {
+                let (radix, digits) = parser::split_radix(text);
+
+                match i128::from_str_radix(digits, radix) {
+                    Ok(val) => Ok(Value::Int(val)),
+                    Err(_) if digits.chars().all(|c| c.is_digit(radix)) => Err(EvalError::Overflow{span}),
+                    Err(_) => Err(EvalError::InvalidLiteral(String::from(text)))
+                }
+            },
+            Mode::Decimal{scale, rounding} => {
+                let val = exact_literal(text, span)?;
+                Ok(Value::Decimal(Decimal::from_rational(&val, scale, rounding)))
+            }
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Value::Float(val) => *val,
+            Value::Exact(val) => val.to_f64().unwrap_or(f64::NAN),
+            Value::Int(val) => *val as f64,
+            Value::Decimal(val) => val.to_rational().to_f64().unwrap_or(f64::NAN),
+            Value::Quantity(val) => val.value.to_f64()
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        match self {
+            Value::Float(val) => *val == 0.0,
+            Value::Exact(val) => val.is_zero(),
+            Value::Int(val) => *val == 0,
+            Value::Decimal(val) => val.is_zero(),
+            Value::Quantity(val) => val.value.is_zero()
+        }
+    }
+
+    /// Negates the value, `span` is the negation for reporting `-i128::MIN`
+    pub fn neg(self, span: Span) -> Result<Value, EvalError> {
+        match self {
+            Value::Float(val) => Ok(Value::Float(-val)),
+            Value::Exact(val) => Ok(Value::Exact(-val)),
+            Value::Int(val) => val.checked_neg().map(Value::Int).ok_or(EvalError::Overflow{span}),
+            Value::Decimal(val) => Ok(Value::Decimal(val.neg())),
+            Value::Quantity(val) if val.absolute => Err(EvalError::OffsetArithmetic{span}),
+            Value::Quantity(mut val) => {
+                val.value = val.value.neg(span)?;
+                Ok(Value::Quantity(val))
+            }
+        }
+    }
+
+    /// Applies `op`, `span` is the whole subexpression for error reporting
+    pub fn binary(op: BinaryOp, lhs: Value, rhs: Value, span: Span) -> Result<Value, EvalError> {
+        match (lhs, rhs) {
+            (lhs @ Value::Quantity(_), rhs) | (lhs, rhs @ Value::Quantity(_)) => units::binary(op, lhs, rhs, span),
+            (Value::Int(lhs), Value::Int(rhs)) => int_binary(op, lhs, rhs, span),
+            (Value::Float(lhs), rhs) => float_binary(op, lhs, rhs.to_f64(), span),
+            (lhs, Value::Float(rhs)) => float_binary(op, lhs.to_f64(), rhs, span),
+            (Value::Decimal(lhs), rhs) => {
+                let scale = match &rhs {
+                    Value::Decimal(rhs) => lhs.scale().max(rhs.scale()),
+                    _ => lhs.scale()
+                };
+
+                decimal_binary(op, lhs.to_rational(), rhs.to_exact(), scale, lhs.rounding(), span)
+            },
+            (lhs, Value::Decimal(rhs)) => decimal_binary(op, lhs.to_exact(), rhs.to_rational(), rhs.scale(), rhs.rounding(), span),
+            (lhs, rhs) => exact_binary(op, lhs.to_exact(), rhs.to_exact(), span)
+        }
+    }
+
+    /// Applies the bitwise `op`, the result has the number type that `binary`
+    /// would give for the operands
+    pub fn bitwise(op: BitwiseOp, lhs: Value, rhs: Value, span: Span) -> Result<Value, EvalError> {
+        let (a, b) = match (lhs.integral(), rhs.integral()) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return Err(EvalError::NotAnInteger{span})
+        };
+
+        if (op == BitwiseOp::Shl || op == BitwiseOp::Shr) && b.is_negative() {
+            return Err(EvalError::NegativeShift{span});
+        }
+
+        let result = match op {
+            BitwiseOp::And => a & b,
+            BitwiseOp::Or => a | b,
+            BitwiseOp::Xor => a ^ b,
+            BitwiseOp::Shl if a.is_zero() => a,
+            BitwiseOp::Shl => match b.to_u64() {
+                Some(shift) if a.bits().saturating_add(shift) <= MAX_EXACT_BITS => a << shift,
+                _ => return Err(EvalError::Overflow{span})
+            },
+            // Shifting by more than the bit length gives 0 or -1 either way
+            BitwiseOp::Shr => {
+                let shift = b.to_u64().unwrap_or(u64::MAX).min(a.bits() + 1);
+                a >> shift
+            }
+        };
+
+        let like = match (&lhs, &rhs) {
+            (Value::Float(_), _) | (Value::Decimal(_), Value::Int(_) | Value::Exact(_) | Value::Decimal(_)) => &lhs,
+            (_, Value::Float(_)) | (_, Value::Decimal(_)) => &rhs,
+            (Value::Exact(_), _) => &lhs,
+            _ => &rhs
+        };
+
+        like.with_integer(result, span)
+    }
+
+    /// Bitwise complement, `~x == -x - 1`
+    pub fn not(self, span: Span) -> Result<Value, EvalError> {
+        match self.integral() {
+            Some(val) => self.with_integer(!val, span),
+            None => Err(EvalError::NotAnInteger{span})
+        }
+    }
+
+    /// The value as a `BigInt` if it has no fractional part, quantities are
+    /// never integers
+    pub fn integral(&self) -> Option<BigInt> {
+        match self {
+            Value::Float(val) if val.fract() == 0.0 => BigInt::from_f64(*val),
+            Value::Float(_) | Value::Quantity(_) => None,
+            Value::Int(val) => Some(BigInt::from(*val)),
+            val => Some(val.to_exact()).filter(|val| val.is_integer()).map(|val| val.to_integer())
+        }
+    }
+
+    /// Converts the integer `val` to the number type of `self`
+    fn with_integer(&self, val: BigInt, span: Span) -> Result<Value, EvalError> {
+        match self {
+            Value::Float(_) => match val.to_f64() {
+                Some(val) if val.is_finite() => Ok(Value::Float(val)),
+                _ => Err(EvalError::Overflow{span})
+            },
+            Value::Exact(_) => Ok(Value::Exact(BigRational::from_integer(val))),
+            Value::Int(_) => val.to_i128().map(Value::Int).ok_or(EvalError::Overflow{span}),
+            Value::Decimal(like) => Ok(Value::Decimal(Decimal::from_rational(&BigRational::from_integer(val), like.scale(), like.rounding()))),
+            Value::Quantity(like) => like.value.with_integer(val, span)
+        }
+    }
+
+    /// The exact value, `None` for infinities and NaN
+    pub fn to_rational(&self) -> Option<BigRational> {
+        match self {
+            Value::Float(val) => BigRational::from_float(*val),
+            Value::Quantity(val) => val.value.to_rational(),
+            val => Some(val.to_exact())
+        }
+    }
+
+    /// Not called on `Float` values, which have no exact counterpart in general
+    fn to_exact(&self) -> BigRational {
+        match self {
+            Value::Exact(val) => val.clone(),
+            Value::Int(val) => BigRational::from_integer(BigInt::from(*val)),
+            Value::Decimal(val) => val.to_rational(),
+            Value::Float(val) => BigRational::from_float(*val).unwrap_or_default(),
+            Value::Quantity(val) => val.value.to_exact()
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Float(val) => write!(f, "{}", val),
+            Value::Exact(val) => write!(f, "{}", val),
+            Value::Int(val) => write!(f, "{}", val),
+            Value::Decimal(val) => write!(f, "{}", val),
+            Value::Quantity(val) => write!(f, "{}", val)
+        }
+    }
+}